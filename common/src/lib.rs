@@ -1,2 +1,3 @@
 pub mod parsing;
+#[cfg(feature = "prover_state")]
 pub mod prover_state;
@@ -2,6 +2,9 @@ use std::path::PathBuf;
 
 use clap::{Parser, Subcommand, ValueHint};
 use common::prover_state::cli::CliProverStateConfig;
+use rpc::{block_id::BlockId, client::RpcArgs, header_defaults::HeaderDefaults};
+
+use crate::prove::ArtifactArgs;
 
 /// zero-bin leader config
 #[derive(Parser)]
@@ -16,9 +19,30 @@ pub(crate) struct Cli {
     // mode.
     #[clap(flatten)]
     pub(crate) prover_state_config: CliProverStateConfig,
+
+    /// If provided, periodically write liveness and progress information to
+    /// this file.
+    #[arg(long, value_hint = ValueHint::FilePath)]
+    pub(crate) heartbeat_file: Option<PathBuf>,
+    /// The interval, in seconds, at which the heartbeat file is written.
+    #[arg(long, default_value_t = 10)]
+    pub(crate) heartbeat_interval: u64,
+    /// If provided, count the blocks proven more than this many seconds after
+    /// their timestamp as latency SLO breaches.
+    #[arg(long)]
+    pub(crate) latency_slo: Option<u64>,
+
+    /// A file containing `RUST_LOG`-style directives, re-read on `SIGHUP` to
+    /// change the log filter without restarting the leader. Unix only.
+    #[arg(long, value_hint = ValueHint::FilePath)]
+    pub(crate) log_filter_file: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub(crate) artifacts: ArtifactArgs,
 }
 
 #[derive(Subcommand)]
+#[allow(clippy::large_enum_variant)]
 pub(crate) enum Command {
     /// Reads input from stdin and writes output to stdout.
     Stdio {
@@ -28,15 +52,27 @@ pub(crate) enum Command {
     },
     /// Reads input from a Jerigon node and writes output to stdout.
     Jerigon {
-        // The Jerigon RPC URL.
-        #[arg(long, short = 'u', value_hint = ValueHint::Url)]
-        rpc_url: String,
-        /// The block number for which to generate a proof.
-        #[arg(short, long)]
-        block_number: u64,
-        /// The checkpoint block number.
-        #[arg(short, long, default_value_t = 0)]
-        checkpoint_block_number: u64,
+        #[command(flatten)]
+        rpc: RpcArgs,
+        /// The block for which to generate a proof: a number, a hash, or one
+        /// of `latest`, `safe`, `finalized` and `earliest`.
+        #[arg(short, long, visible_alias = "block-number")]
+        block: BlockId,
+        /// The checkpoint block, in the same forms as the block.
+        #[arg(
+            short,
+            long,
+            visible_alias = "checkpoint-block-number",
+            default_value = "0"
+        )]
+        checkpoint: BlockId,
+        /// The chain to fetch from: `mainnet`, `sepolia`, `holesky`,
+        /// `op-stack`, or a JSON chain spec describing the fork schedule of
+        /// another chain.
+        #[arg(long, visible_alias = "chain-spec", value_name = "NAME|PATH")]
+        chain: Option<String>,
+        #[command(flatten)]
+        header_defaults: HeaderDefaults,
         /// The previous proof output.
         #[arg(long, short = 'f', value_hint = ValueHint::FilePath)]
         previous_proof: Option<PathBuf>,
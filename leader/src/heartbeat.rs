@@ -0,0 +1,400 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Result;
+use ethereum_types::U256;
+use rpc::{client::RpcClient, health::EndpointStatus};
+use serde::Serialize;
+use tracing::warn;
+
+/// A point-in-time view of the leader's progress.
+#[derive(Serialize, Debug, Clone)]
+pub(crate) struct ProgressSnapshot {
+    pid: u32,
+    /// Unix timestamp (seconds) at which the leader started.
+    started_at: u64,
+    /// Unix timestamp (seconds) at which this snapshot was taken.
+    updated_at: u64,
+    /// Blocks currently being proven.
+    in_progress: Vec<u64>,
+    /// The most recently proven block.
+    last_proven: Option<u64>,
+    proofs_completed: u64,
+    proofs_failed: u64,
+    /// The health of the RPC endpoints the leader fetches from, if any.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    rpc_endpoints: Vec<EndpointStatus>,
+}
+
+/// The number of finished proofs kept for [`Overview::recent_proofs`].
+const RECENT_PROOFS: usize = 20;
+/// The number of proven blocks over which latency percentiles are computed.
+const LATENCY_WINDOW: usize = 1000;
+
+/// A summary of the leader's work, for operations dashboards.
+#[derive(Serialize, Debug)]
+pub(crate) struct Overview {
+    /// Unix timestamp (seconds) at which the leader started.
+    started_at: u64,
+    /// The highest block the leader has been asked to prove.
+    head: Option<u64>,
+    /// The most recently proven block.
+    last_proven: Option<u64>,
+    /// The number of blocks currently being proven.
+    backlog: usize,
+    proofs_completed: u64,
+    proofs_failed: u64,
+    /// The most recently finished proofs, newest first.
+    recent_proofs: Vec<RecentProof>,
+    latency: LatencySummary,
+    /// The health of the RPC endpoints the leader fetches from, if any.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    rpc_endpoints: Vec<EndpointStatus>,
+}
+
+/// End-to-end latency of proven blocks, from the block's timestamp to the
+/// completion of its proof.
+#[derive(Serialize, Debug)]
+struct LatencySummary {
+    /// The number of proven blocks the percentiles are computed over.
+    samples: usize,
+    p50_secs: Option<f64>,
+    p90_secs: Option<f64>,
+    p99_secs: Option<f64>,
+    max_secs: Option<f64>,
+    slo_secs: Option<f64>,
+    /// The number of blocks proven later than `slo_secs` after their
+    /// timestamp, since the leader started.
+    slo_breaches: u64,
+}
+
+#[derive(Serialize, Debug, Clone)]
+struct RecentProof {
+    block_number: u64,
+    success: bool,
+    /// Unix timestamp (seconds) at which proving finished.
+    finished_at: u64,
+    duration_secs: f64,
+    /// The time between the block's timestamp and the leader receiving its
+    /// witness.
+    witness_delay_secs: f64,
+    /// The time between the block's timestamp and the end of proving.
+    end_to_end_secs: f64,
+}
+
+#[derive(Debug)]
+struct InFlight {
+    started: Instant,
+    /// The block's timestamp, in Unix seconds.
+    sealed_at: u64,
+    /// Unix time (seconds) at which the leader received the block's witness.
+    received_at: f64,
+}
+
+#[derive(Debug)]
+struct State {
+    pid: u32,
+    started_at: u64,
+    /// Blocks currently being proven.
+    in_progress: BTreeMap<u64, InFlight>,
+    head: Option<u64>,
+    last_proven: Option<u64>,
+    proofs_completed: u64,
+    proofs_failed: u64,
+    recent_proofs: VecDeque<RecentProof>,
+    /// End-to-end latencies of the last [`LATENCY_WINDOW`] proven blocks.
+    latencies: VecDeque<f64>,
+    latency_slo: Option<Duration>,
+    slo_breaches: u64,
+    rpc: Option<RpcClient>,
+}
+
+impl State {
+    fn rpc_endpoints(&self) -> Vec<EndpointStatus> {
+        self.rpc
+            .as_ref()
+            .map(RpcClient::endpoint_health)
+            .unwrap_or_default()
+    }
+}
+
+/// Tracks proving progress across all requests handled by the leader.
+///
+/// Cheap to clone; all clones share the same state.
+#[derive(Debug, Clone)]
+pub(crate) struct Progress(Arc<Mutex<State>>);
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+fn unix_now_f64() -> f64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs_f64()
+}
+
+/// Returns the nearest-rank `p`th percentile of the ascending `sorted`.
+fn percentile(sorted: &[f64], p: f64) -> Option<f64> {
+    let rank = (p / 100.0 * sorted.len() as f64).ceil() as usize;
+    sorted.get(rank.max(1) - 1).copied()
+}
+
+impl Progress {
+    /// Creates a tracker which counts the blocks proven later than
+    /// `latency_slo` after their timestamp as SLO breaches.
+    pub(crate) fn new(latency_slo: Option<Duration>) -> Self {
+        Self(Arc::new(Mutex::new(State {
+            pid: std::process::id(),
+            started_at: unix_now(),
+            in_progress: BTreeMap::new(),
+            head: None,
+            last_proven: None,
+            proofs_completed: 0,
+            proofs_failed: 0,
+            recent_proofs: VecDeque::with_capacity(RECENT_PROOFS),
+            latencies: VecDeque::with_capacity(LATENCY_WINDOW),
+            latency_slo,
+            slo_breaches: 0,
+            rpc: None,
+        })))
+    }
+
+    /// Reports the health of the endpoints of `client` along with the
+    /// progress.
+    pub(crate) fn track_rpc(&self, client: &RpcClient) {
+        self.0.lock().unwrap().rpc = Some(client.clone());
+    }
+
+    /// Records that proving of the given block has started, now that its
+    /// witness is at hand. `timestamp` is the block's timestamp.
+    pub(crate) fn start(&self, block_number: U256, timestamp: U256) {
+        let block_number = block_number.as_u64();
+        let mut state = self.0.lock().unwrap();
+        state.in_progress.insert(
+            block_number,
+            InFlight {
+                started: Instant::now(),
+                sealed_at: timestamp.low_u64(),
+                received_at: unix_now_f64(),
+            },
+        );
+        state.head = state.head.max(Some(block_number));
+    }
+
+    /// Records that proving of the given block has finished.
+    pub(crate) fn finish(&self, block_number: U256, success: bool) {
+        let block_number = block_number.as_u64();
+        let mut state = self.0.lock().unwrap();
+        let Some(in_flight) = state.in_progress.remove(&block_number) else {
+            return;
+        };
+        let finished_at = unix_now_f64();
+        // Clamped, as clock skew may put the block's timestamp in the future.
+        let end_to_end_secs = (finished_at - in_flight.sealed_at as f64).max(0.0);
+        if success {
+            state.proofs_completed += 1;
+            state.last_proven = Some(block_number);
+            if state.latencies.len() == LATENCY_WINDOW {
+                state.latencies.pop_front();
+            }
+            state.latencies.push_back(end_to_end_secs);
+            if state
+                .latency_slo
+                .is_some_and(|slo| end_to_end_secs > slo.as_secs_f64())
+            {
+                state.slo_breaches += 1;
+                warn!(
+                    "Block {block_number} was proven {end_to_end_secs:.0}s after its timestamp, \
+                     breaching the latency SLO"
+                );
+            }
+        } else {
+            state.proofs_failed += 1;
+        }
+
+        if state.recent_proofs.len() == RECENT_PROOFS {
+            state.recent_proofs.pop_back();
+        }
+        state.recent_proofs.push_front(RecentProof {
+            block_number,
+            success,
+            finished_at: finished_at as u64,
+            duration_secs: in_flight.started.elapsed().as_secs_f64(),
+            witness_delay_secs: (in_flight.received_at - in_flight.sealed_at as f64).max(0.0),
+            end_to_end_secs,
+        });
+    }
+
+    pub(crate) fn snapshot(&self) -> ProgressSnapshot {
+        let state = self.0.lock().unwrap();
+        ProgressSnapshot {
+            pid: state.pid,
+            started_at: state.started_at,
+            updated_at: unix_now(),
+            in_progress: state.in_progress.keys().copied().collect(),
+            last_proven: state.last_proven,
+            proofs_completed: state.proofs_completed,
+            proofs_failed: state.proofs_failed,
+            rpc_endpoints: state.rpc_endpoints(),
+        }
+    }
+
+    pub(crate) fn overview(&self) -> Overview {
+        let state = self.0.lock().unwrap();
+        let mut latencies: Vec<_> = state.latencies.iter().copied().collect();
+        latencies.sort_by(f64::total_cmp);
+        Overview {
+            started_at: state.started_at,
+            head: state.head,
+            last_proven: state.last_proven,
+            backlog: state.in_progress.len(),
+            proofs_completed: state.proofs_completed,
+            proofs_failed: state.proofs_failed,
+            recent_proofs: state.recent_proofs.iter().cloned().collect(),
+            latency: LatencySummary {
+                samples: latencies.len(),
+                p50_secs: percentile(&latencies, 50.0),
+                p90_secs: percentile(&latencies, 90.0),
+                p99_secs: percentile(&latencies, 99.0),
+                max_secs: latencies.last().copied(),
+                slo_secs: state.latency_slo.map(|slo| slo.as_secs_f64()),
+                slo_breaches: state.slo_breaches,
+            },
+            rpc_endpoints: state.rpc_endpoints(),
+        }
+    }
+
+    /// Atomically replaces the heartbeat file with the current snapshot.
+    fn write(&self, path: &Path) -> Result<()> {
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, serde_json::to_vec(&self.snapshot())?)?;
+        fs::rename(tmp, path)?;
+        Ok(())
+    }
+
+    /// Spawns a task which rewrites the heartbeat file every `interval`, so
+    /// that external supervisors can detect a wedged leader by its age.
+    pub(crate) fn spawn_heartbeat(&self, path: PathBuf, interval: Duration) {
+        let progress = self.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                if let Err(e) = progress.write(&path) {
+                    warn!("failed to write heartbeat file {}: {e:#}", path.display());
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn it_summarizes_progress() {
+        let progress = Progress::new(None);
+        let now = unix_now().into();
+        progress.start(1.into(), now);
+        progress.start(2.into(), now);
+        progress.start(3.into(), now);
+        progress.finish(1.into(), true);
+        progress.finish(2.into(), false);
+        // Blocks that were never started are ignored.
+        progress.finish(4.into(), true);
+
+        let overview = progress.overview();
+        assert_eq!(overview.head, Some(3));
+        assert_eq!(overview.last_proven, Some(1));
+        assert_eq!(overview.backlog, 1);
+        assert_eq!((overview.proofs_completed, overview.proofs_failed), (1, 1));
+        let recent: Vec<_> = overview
+            .recent_proofs
+            .iter()
+            .map(|proof| (proof.block_number, proof.success))
+            .collect();
+        assert_eq!(recent, [(2, false), (1, true)]);
+        assert_eq!(overview.latency.samples, 1);
+    }
+
+    #[test]
+    fn it_computes_nearest_rank_percentiles() {
+        let sorted: Vec<_> = (1..=10).map(f64::from).collect();
+        assert_eq!(percentile(&sorted, 50.0), Some(5.0));
+        assert_eq!(percentile(&sorted, 90.0), Some(9.0));
+        assert_eq!(percentile(&sorted, 99.0), Some(10.0));
+        assert_eq!(percentile(&sorted, 0.0), Some(1.0));
+        assert_eq!(percentile(&[3.0], 50.0), Some(3.0));
+        assert_eq!(percentile(&[], 50.0), None);
+    }
+
+    #[test]
+    fn it_counts_slo_breaches() {
+        let progress = Progress::new(Some(Duration::from_secs(60)));
+        let now = unix_now();
+        // Proven two minutes after its timestamp.
+        progress.start(1.into(), (now - 120).into());
+        progress.finish(1.into(), true);
+        progress.start(2.into(), now.into());
+        progress.finish(2.into(), true);
+        // Failed proofs are not held to the SLO.
+        progress.start(3.into(), (now - 120).into());
+        progress.finish(3.into(), false);
+
+        let latency = progress.overview().latency;
+        assert_eq!(latency.samples, 2);
+        assert_eq!(latency.slo_secs, Some(60.0));
+        assert_eq!(latency.slo_breaches, 1);
+        assert!(latency.max_secs.unwrap() >= 120.0);
+    }
+
+    #[test]
+    fn it_reports_the_health_of_rpc_endpoints() {
+        #[derive(clap::Parser)]
+        struct Cli {
+            #[command(flatten)]
+            rpc: rpc::client::RpcArgs,
+        }
+        let cli = <Cli as clap::Parser>::parse_from(["leader", "--rpc-url", "http://127.0.0.1:1/"]);
+        let client = cli.rpc.client(&cli.rpc.profile().unwrap()).unwrap();
+
+        let progress = Progress::new(None);
+        let snapshot = serde_json::to_value(progress.snapshot()).unwrap();
+        assert!(snapshot.get("rpc_endpoints").is_none());
+
+        progress.track_rpc(&client);
+        let snapshot = serde_json::to_value(progress.snapshot()).unwrap();
+        assert_eq!(snapshot["rpc_endpoints"].as_array().unwrap().len(), 1);
+        assert_eq!(snapshot["rpc_endpoints"][0]["consecutive_failures"], 0);
+        assert_eq!(progress.overview().rpc_endpoints.len(), 1);
+    }
+
+    #[test]
+    fn it_replaces_the_heartbeat_file() {
+        let dir = std::env::temp_dir().join(format!("leader-heartbeat-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("heartbeat.json");
+        fs::write(&path, "stale").unwrap();
+
+        let progress = Progress::new(None);
+        progress.start(7.into(), unix_now().into());
+        progress.write(&path).unwrap();
+
+        let written: serde_json::Value = serde_json::from_slice(&fs::read(&path).unwrap()).unwrap();
+        assert_eq!(written["pid"], std::process::id());
+        assert_eq!(written["in_progress"], serde_json::json!([7]));
+        assert!(!path.with_extension("tmp").exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -1,30 +1,70 @@
 use std::{net::SocketAddr, path::PathBuf, sync::Arc};
 
 use anyhow::{bail, Result};
-use axum::{http::StatusCode, routing::post, Json, Router};
+use axum::{
+    http::StatusCode,
+    routing::{get, post},
+    Json, Router,
+};
 use ethereum_types::U256;
 use paladin::runtime::Runtime;
 use proof_gen::{proof_types::GeneratedBlockProof, types::PlonkyProofIntern};
 use prover::ProverInput;
 use serde::{Deserialize, Serialize};
 use serde_json::to_writer;
+use tokio_util::sync::CancellationToken;
 use tracing::{debug, error, info};
 
+use crate::{
+    heartbeat::Progress,
+    prove::{prove_block, ArtifactArgs},
+};
+
 /// The main function for the HTTP mode.
-pub(crate) async fn http_main(runtime: Runtime, port: u16, output_dir: PathBuf) -> Result<()> {
+pub(crate) async fn http_main(
+    runtime: Runtime,
+    port: u16,
+    output_dir: PathBuf,
+    progress: Progress,
+    artifacts: ArtifactArgs,
+    cancel: CancellationToken,
+) -> Result<()> {
     let addr = SocketAddr::from(([0, 0, 0, 0], port));
     debug!("listening on {}", addr);
 
     let runtime = Arc::new(runtime);
-    let app = Router::new().route(
-        "/prove",
-        post({
-            let runtime = runtime.clone();
-            move |body| prove(body, runtime, output_dir.clone())
-        }),
-    );
+    let app = Router::new()
+        .route(
+            "/prove",
+            post({
+                let runtime = runtime.clone();
+                let progress = progress.clone();
+                let cancel = cancel.clone();
+                move |body| {
+                    prove(
+                        body,
+                        runtime,
+                        output_dir.clone(),
+                        progress,
+                        artifacts.clone(),
+                        cancel.clone(),
+                    )
+                }
+            }),
+        )
+        .route("/livez", {
+            let progress = progress.clone();
+            get(move || async move { Json(progress.snapshot()) })
+        })
+        .route(
+            "/api/overview",
+            get(move || async move { Json(progress.overview()) }),
+        );
     let listener = tokio::net::TcpListener::bind(&addr).await?;
-    Ok(axum::serve(listener, app).await?)
+    axum::serve(listener, app)
+        .with_graceful_shutdown(async move { cancel.cancelled().await })
+        .await?;
+    runtime.close().await
 }
 
 /// Writes the generated block proof to a file.
@@ -60,12 +100,25 @@ async fn prove(
     Json(payload): Json<HttpProverInput>,
     runtime: Arc<Runtime>,
     output_dir: PathBuf,
+    progress: Progress,
+    artifacts: ArtifactArgs,
+    cancel: CancellationToken,
 ) -> StatusCode {
     debug!("Received payload: {:#?}", payload);
 
     let block_number = payload.prover_input.get_block_number();
 
-    match payload.prover_input.prove(&runtime, payload.previous).await {
+    let proof = prove_block(
+        &runtime,
+        payload.prover_input,
+        payload.previous,
+        &progress,
+        &artifacts,
+        &cancel,
+    )
+    .await;
+
+    match proof {
         Ok(b_proof) => match write_to_file(output_dir, block_number, &b_proof) {
             Ok(file) => {
                 info!("Successfully wrote proof to {}", file.display());
@@ -1,11 +1,58 @@
-use tracing_subscriber::{prelude::*, util::SubscriberInitExt, EnvFilter};
-pub(crate) fn tracing() {
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use tracing::info;
+use tracing_subscriber::{prelude::*, reload, util::SubscriberInitExt, EnvFilter, Registry};
+
+/// Handle used to swap the log filter of the running leader.
+pub(crate) type LogFilterHandle = reload::Handle<EnvFilter, Registry>;
+
+pub(crate) fn tracing() -> LogFilterHandle {
+    let (filter, handle) = reload::Layer::new(EnvFilter::from_default_env());
     tracing_subscriber::Registry::default()
         .with(
             tracing_subscriber::fmt::layer()
                 .with_ansi(false)
                 .compact()
-                .with_filter(EnvFilter::from_default_env()),
+                .with_filter(filter),
         )
         .init();
+    handle
+}
+
+/// Replaces the active log filter with the given `RUST_LOG`-style directives.
+pub(crate) fn reload_log_filter(handle: &LogFilterHandle, directives: &str) -> Result<()> {
+    let filter = EnvFilter::try_new(directives.trim())
+        .with_context(|| format!("parsing log filter {directives:?}"))?;
+    handle.reload(filter)?;
+    info!("log filter set to {:?}", directives.trim());
+    Ok(())
+}
+
+/// Re-reads the log filter directives from `path` whenever the process
+/// receives `SIGHUP`.
+#[cfg(unix)]
+pub(crate) fn spawn_sighup_reloader(handle: LogFilterHandle, path: PathBuf) -> Result<()> {
+    use tokio::signal::unix::{signal, SignalKind};
+    use tracing::warn;
+
+    let mut hangup = signal(SignalKind::hangup())?;
+    tokio::spawn(async move {
+        while hangup.recv().await.is_some() {
+            let result = std::fs::read_to_string(&path)
+                .with_context(|| format!("reading log filter file {}", path.display()))
+                .and_then(|directives| reload_log_filter(&handle, &directives));
+            if let Err(e) = result {
+                warn!("failed to reload log filter: {e:#}");
+            }
+        }
+    });
+    Ok(())
+}
+
+/// `SIGHUP` only exists on Unix, so the log filter cannot be reloaded
+/// elsewhere.
+#[cfg(not(unix))]
+pub(crate) fn spawn_sighup_reloader(_handle: LogFilterHandle, _path: PathBuf) -> Result<()> {
+    anyhow::bail!("--log-filter-file is only supported on Unix, as it relies on SIGHUP")
 }
@@ -7,24 +7,45 @@ use std::{
 use anyhow::Result;
 use paladin::runtime::Runtime;
 use proof_gen::types::PlonkyProofIntern;
+use rpc::{error::RpcError, FetchProverInputRequest};
+use tracing::warn;
+
+use crate::{
+    heartbeat::Progress,
+    prove::{prove_block, ArtifactArgs},
+};
 
 /// The main function for the jerigon mode.
 pub(crate) async fn jerigon_main(
     runtime: Runtime,
-    rpc_url: &str,
-    block_number: u64,
-    checkpoint_block_number: u64,
+    request: FetchProverInputRequest<'_>,
     previous: Option<PlonkyProofIntern>,
     proof_output_path_opt: Option<PathBuf>,
+    progress: Progress,
+    artifacts: ArtifactArgs,
 ) -> Result<()> {
-    let prover_input = rpc::fetch_prover_input(rpc::FetchProverInputRequest {
-        rpc_url,
-        block_number,
-        checkpoint_block_number,
-    })
-    .await?;
-
-    let proof = prover_input.prove(&runtime, previous).await;
+    let (client, cancel) = (request.client, request.cancel);
+    let proof = async {
+        let prover_input = rpc::fetch_prover_input(request).await;
+        if let Some(summary) = client.metrics_summary() {
+            eprint!("{summary}");
+        }
+        let transient = prover_input.as_ref().err().and_then(RpcError::find);
+        if transient.is_some_and(RpcError::is_retryable) {
+            warn!("Fetching failed with a transient error; rerunning may succeed");
+        }
+        let prover_input = prover_input?;
+        prove_block(
+            &runtime,
+            prover_input,
+            previous,
+            &progress,
+            &artifacts,
+            cancel,
+        )
+        .await
+    }
+    .await;
     runtime.close().await?;
 
     let proof = serde_json::to_vec(&proof?.intern)?;
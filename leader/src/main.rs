@@ -1,4 +1,4 @@
-use std::{fs::File, path::PathBuf};
+use std::{fs::File, path::PathBuf, time::Duration};
 
 use anyhow::Result;
 use clap::Parser;
@@ -8,11 +8,14 @@ use dotenvy::dotenv;
 use ops::register;
 use paladin::runtime::Runtime;
 use proof_gen::types::PlonkyProofIntern;
+use rpc::{signal::cancel_on_ctrl_c, FetchProverInputRequest};
 
 mod cli;
+mod heartbeat;
 mod http;
 mod init;
 mod jerigon;
+mod prove;
 mod stdio;
 
 fn get_previous_proof(path: Option<PathBuf>) -> Result<Option<PlonkyProofIntern>> {
@@ -30,9 +33,13 @@ fn get_previous_proof(path: Option<PathBuf>) -> Result<Option<PlonkyProofIntern>
 #[tokio::main]
 async fn main() -> Result<()> {
     dotenv().ok();
-    init::tracing();
+    let log_filter = init::tracing();
 
     let args = cli::Cli::parse();
+    if let Some(log_filter_file) = args.log_filter_file {
+        init::spawn_sighup_reloader(log_filter.clone(), log_filter_file)?;
+    }
+
     if let paladin::config::Runtime::InMemory = args.paladin.runtime {
         // If running in emulation mode, we'll need to initialize the prover
         // state here.
@@ -46,10 +53,17 @@ async fn main() -> Result<()> {
 
     let runtime = Runtime::from_config(&args.paladin, register()).await?;
 
+    let cancel = cancel_on_ctrl_c();
+
+    let progress = heartbeat::Progress::new(args.latency_slo.map(Duration::from_secs));
+    if let Some(heartbeat_file) = args.heartbeat_file {
+        progress.spawn_heartbeat(heartbeat_file, Duration::from_secs(args.heartbeat_interval));
+    }
+
     match args.command {
         Command::Stdio { previous_proof } => {
             let previous_proof = get_previous_proof(previous_proof)?;
-            stdio::stdio_main(runtime, previous_proof).await?;
+            stdio::stdio_main(runtime, previous_proof, progress, args.artifacts, cancel).await?;
         }
         Command::Http { port, output_dir } => {
             // check if output_dir exists, is a directory, and is writable
@@ -61,24 +75,39 @@ async fn main() -> Result<()> {
                 panic!("output-dir is not a writable directory");
             }
 
-            http::http_main(runtime, port, output_dir).await?;
+            http::http_main(runtime, port, output_dir, progress, args.artifacts, cancel).await?;
         }
         Command::Jerigon {
-            rpc_url,
-            block_number,
-            checkpoint_block_number,
+            rpc,
+            block,
+            checkpoint,
+            chain,
+            header_defaults,
             previous_proof,
             proof_output_path,
         } => {
             let previous_proof = get_previous_proof(previous_proof)?;
+            let profile = rpc.profile()?;
+            let chain_spec = rpc.chain_spec(chain.as_deref(), &profile)?;
+
+            let client = rpc.client(&profile)?;
+            progress.track_rpc(&client);
 
             jerigon::jerigon_main(
                 runtime,
-                &rpc_url,
-                block_number,
-                checkpoint_block_number,
+                FetchProverInputRequest {
+                    client: &client,
+                    block_id: block,
+                    checkpoint_block_id: checkpoint,
+                    chain_spec: &chain_spec,
+                    header_defaults: &header_defaults,
+                    prev_hash_concurrency: rpc.prev_hash_concurrency,
+                    cancel: &cancel,
+                },
                 previous_proof,
                 proof_output_path,
+                progress,
+                args.artifacts,
             )
             .await?;
         }
@@ -0,0 +1,188 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Result;
+use clap::{Args, ValueHint};
+use ethereum_types::U256;
+use paladin::runtime::Runtime;
+use proof_gen::{proof_types::GeneratedBlockProof, types::PlonkyProofIntern};
+use prover::{DecodedProverInput, ProverInput};
+use rpc::trie::keccak;
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, warn};
+
+use crate::heartbeat::Progress;
+
+/// Debugging artifacts written while proving.
+#[derive(Args, Debug, Clone, Default)]
+pub(crate) struct ArtifactArgs {
+    /// If provided, write the input of any block that fails to prove to this
+    /// directory.
+    #[arg(long, value_hint = ValueHint::DirPath)]
+    pub(crate) save_inputs_on_error: Option<PathBuf>,
+    /// If provided, write the decoded inputs of each transaction proof to
+    /// this directory.
+    #[arg(long, value_hint = ValueHint::DirPath)]
+    pub(crate) emit_generation_inputs: Option<PathBuf>,
+}
+
+/// Proves a single block, recording it in `progress`.
+///
+/// If `save_inputs_on_error` is set and proving fails, the prover input is
+/// written to `<save_inputs_on_error>/input-<block_number>.json`, where it can
+/// be fed back to the leader's stdio mode to reproduce the failure. If the
+/// block failed to decode, the failure is also narrowed down to a single
+/// transaction, which the error names, and the input truncated after that
+/// transaction is written to `input-<block_number>-min.json`.
+///
+/// If `emit_generation_inputs` is set, the input of every transaction proof
+/// is written to
+/// `<emit_generation_inputs>/block-<block_number>/txn-<index>.json` before
+/// proving starts.
+pub(crate) async fn prove_block(
+    runtime: &Runtime,
+    input: ProverInput,
+    previous: Option<PlonkyProofIntern>,
+    progress: &Progress,
+    artifacts: &ArtifactArgs,
+    cancel: &CancellationToken,
+) -> Result<GeneratedBlockProof> {
+    let block_number = input.get_block_number();
+    let timestamp = input.other_data.b_data.b_meta.block_timestamp;
+    if !input.overridden.is_empty() {
+        warn!(
+            "Block {block_number} is synthetic, with overridden {}",
+            input.overridden.join(", ")
+        );
+    }
+    // The input is consumed by proving, so keep a serialized copy around.
+    let saved_input = artifacts
+        .save_inputs_on_error
+        .as_ref()
+        .map(|_| serde_json::to_vec(&input))
+        .transpose()?;
+
+    progress.start(block_number, timestamp);
+    let mut decoding_failed = false;
+    let proof = async {
+        let decoded = input.decode().inspect_err(|_| decoding_failed = true)?;
+        if let Some(dir) = &artifacts.emit_generation_inputs {
+            if let Err(e) = write_generation_inputs(dir, &decoded) {
+                error!("Failed to write generation inputs of block {block_number}: {e}");
+            }
+        }
+        decoded.prove(runtime, previous, cancel).await
+    }
+    .await;
+    progress.finish(block_number, proof.is_ok());
+
+    if let (Err(_), Some(dir), Some(saved_input)) =
+        (&proof, &artifacts.save_inputs_on_error, saved_input)
+    {
+        let path = dir.join(format!("input-{block_number}.json"));
+        match fs::create_dir_all(dir).and_then(|()| fs::write(&path, &saved_input)) {
+            Ok(()) => info!(
+                "Saved input of failed block {block_number} to {}",
+                path.display()
+            ),
+            Err(e) => error!("Failed to save input of block {block_number}: {e}"),
+        }
+
+        if decoding_failed {
+            if let Some(culprit) = localize_decoding_failure(dir, block_number, &saved_input) {
+                return proof.map_err(|e| e.context(culprit));
+            }
+        }
+    }
+
+    proof
+}
+
+/// Narrows a decoding failure down to a transaction, writing the input
+/// truncated after it to `<dir>/input-<block_number>-min.json`, and returns a
+/// description of the transaction.
+fn localize_decoding_failure(dir: &Path, block_number: U256, saved_input: &[u8]) -> Option<String> {
+    let (index, minimized) = match minimize_decoding_failure(saved_input) {
+        Ok(Some(found)) => found,
+        Ok(None) => {
+            warn!("Block {block_number} fails to decode even without transactions");
+            return None;
+        }
+        Err(e) => {
+            error!("Failed to minimize input of block {block_number}: {e:#}");
+            return None;
+        }
+    };
+
+    let path = dir.join(format!("input-{block_number}-min.json"));
+    match serde_json::to_vec(&minimized)
+        .map_err(anyhow::Error::from)
+        .and_then(|bytes| Ok(fs::write(&path, bytes)?))
+    {
+        Ok(()) => info!(
+            "Saved input of block {block_number} up to transaction {index} to {}",
+            path.display()
+        ),
+        Err(e) => error!("Failed to save minimized input of block {block_number}: {e}"),
+    }
+
+    let txn = &minimized.block_trace.txn_info[index];
+    let accounts: Vec<_> = txn.traces.keys().map(|a| format!("{a:?}")).collect();
+    Some(format!(
+        "transaction {index} ({:?}) is the first that fails to decode; it touches {}",
+        keccak(&txn.meta.new_txn_trie_node_byte),
+        accounts.join(", ")
+    ))
+}
+
+/// Finds the first transaction whose inclusion makes the block fail to
+/// decode, by bisecting over prefixes of the block's
+/// transactions. The decoder processes transactions in order, so a prefix
+/// fails exactly when it includes that transaction.
+///
+/// Returns the index of that transaction along with the input truncated
+/// after it, which reproduces the failure, or `None` if the block fails to
+/// decode even without transactions.
+fn minimize_decoding_failure(saved_input: &[u8]) -> Result<Option<(usize, ProverInput)>> {
+    let truncated = |len: usize| -> Result<ProverInput> {
+        let mut input: ProverInput = serde_json::from_slice(saved_input)?;
+        input.block_trace.txn_info.truncate(len);
+        Ok(input)
+    };
+    let fails = |len: usize| -> Result<bool> { Ok(truncated(len)?.decode().is_err()) };
+
+    let len = truncated(usize::MAX)?.block_trace.txn_info.len();
+    if len == 0 || fails(0)? {
+        return Ok(None);
+    }
+    // The full block fails, and the empty prefix does not.
+    let (mut passing, mut failing) = (0, len);
+    while failing - passing > 1 {
+        let mid = passing + (failing - passing) / 2;
+        if fails(mid)? {
+            failing = mid;
+        } else {
+            passing = mid;
+        }
+    }
+    Ok(Some((failing - 1, truncated(failing)?)))
+}
+
+fn write_generation_inputs(dir: &Path, decoded: &DecodedProverInput) -> Result<()> {
+    let dir = dir.join(format!("block-{}", decoded.block_number));
+    fs::create_dir_all(&dir)?;
+    for (i, generation_inputs) in decoded.generation_inputs.iter().enumerate() {
+        fs::write(
+            dir.join(format!("txn-{i}.json")),
+            serde_json::to_vec(generation_inputs)?,
+        )?;
+    }
+    info!(
+        "Wrote {} generation inputs to {}",
+        decoded.generation_inputs.len(),
+        dir.display()
+    );
+    Ok(())
+}
@@ -4,18 +4,27 @@ use anyhow::Result;
 use paladin::runtime::Runtime;
 use proof_gen::types::PlonkyProofIntern;
 use prover::ProverInput;
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    heartbeat::Progress,
+    prove::{prove_block, ArtifactArgs},
+};
 
 /// The main function for the stdio mode.
 pub(crate) async fn stdio_main(
     runtime: Runtime,
     previous: Option<PlonkyProofIntern>,
+    progress: Progress,
+    artifacts: ArtifactArgs,
+    cancel: CancellationToken,
 ) -> Result<()> {
     let mut buffer = String::new();
     std::io::stdin().read_to_string(&mut buffer)?;
 
     let des = &mut serde_json::Deserializer::from_str(&buffer);
     let input: ProverInput = serde_path_to_error::deserialize(des)?;
-    let proof = input.prove(&runtime, previous).await;
+    let proof = prove_block(&runtime, input, previous, &progress, &artifacts, &cancel).await;
     runtime.close().await?;
     let proof = proof?;
 
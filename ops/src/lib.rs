@@ -15,15 +15,24 @@ registry!();
 #[derive(Deserialize, Serialize, RemoteExecute)]
 pub struct TxProof;
 
+/// Identifies the transaction being proven in error messages.
+fn txn_label(input: &TxnProofGenIR) -> String {
+    format!(
+        "transaction {} of block {}",
+        input.txn_number_before, input.block_metadata.block_number
+    )
+}
+
 #[cfg(not(feature = "test_only"))]
 impl Operation for TxProof {
     type Input = TxnProofGenIR;
     type Output = proof_gen::proof_types::AggregatableProof;
 
     fn execute(&self, input: Self::Input) -> Result<Self::Output> {
+        let txn = txn_label(&input);
         let proof = common::prover_state::p_manager()
             .generate_txn_proof(input)
-            .map_err(|err| FatalError::from_anyhow(err, FatalStrategy::Terminate))?;
+            .map_err(|err| FatalError::from_anyhow(err.context(txn), FatalStrategy::Terminate))?;
 
         Ok(proof.into())
     }
@@ -35,8 +44,9 @@ impl Operation for TxProof {
     type Output = ();
 
     fn execute(&self, input: Self::Input) -> Result<Self::Output> {
+        let txn = txn_label(&input);
         evm_arithmetization::prover::testing::simulate_execution::<proof_gen::types::Field>(input)
-            .map_err(|err| FatalError::from_anyhow(err, FatalStrategy::Terminate))?;
+            .map_err(|err| FatalError::from_anyhow(err.context(txn), FatalStrategy::Terminate))?;
 
         Ok(())
     }
@@ -1,28 +1,42 @@
-use anyhow::Result;
+#[cfg(feature = "prove")]
+use anyhow::bail;
+use anyhow::{Context, Result};
 use ethereum_types::U256;
 #[cfg(feature = "test_only")]
 use futures::stream::TryStreamExt;
+#[cfg(feature = "prove")]
 use ops::TxProof;
+#[cfg(feature = "prove")]
 use paladin::{
     directive::{Directive, IndexedStream},
     runtime::Runtime,
 };
+#[cfg(feature = "prove")]
 use proof_gen::{proof_types::GeneratedBlockProof, types::PlonkyProofIntern};
 use serde::{Deserialize, Serialize};
+#[cfg(feature = "prove")]
+use tokio_util::sync::CancellationToken;
 use trace_decoder::{
     processed_block_trace::ProcessingMeta,
     trace_protocol::BlockTrace,
-    types::{CodeHash, OtherBlockData},
+    types::{CodeHash, OtherBlockData, TxnProofGenIR},
 };
+#[cfg(feature = "prove")]
 use tracing::info;
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct ProverInput {
     pub block_trace: BlockTrace,
     pub other_data: OtherBlockData,
+    /// The fields of `other_data` that were overridden after fetching, which
+    /// make this the input of a synthetic block rather than of one of the
+    /// chain.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub overridden: Vec<String>,
 }
-fn resolve_code_hash_fn(_: &CodeHash) -> Vec<u8> {
-    todo!()
+/// Called by the decoder for code that is read but missing from the witness.
+fn resolve_code_hash_fn(code_hash: &CodeHash) -> Vec<u8> {
+    panic!("no code for hash {code_hash:?} in the witness")
 }
 
 impl ProverInput {
@@ -30,22 +44,71 @@ impl ProverInput {
         self.other_data.b_data.b_meta.block_number
     }
 
-    #[cfg(not(feature = "test_only"))]
+    /// Decodes the block trace into the inputs of the block's transaction
+    /// proofs.
+    pub fn decode(self) -> Result<DecodedProverInput> {
+        let block_number = self.get_block_number();
+        let generation_inputs = self
+            .block_trace
+            .into_txn_proof_gen_ir(&ProcessingMeta::new(resolve_code_hash_fn), self.other_data)
+            .with_context(|| format!("decoding trace of block {block_number}"))?;
+
+        Ok(DecodedProverInput {
+            block_number,
+            generation_inputs,
+        })
+    }
+
+    #[cfg(feature = "prove")]
     pub async fn prove(
         self,
         runtime: &Runtime,
         previous: Option<PlonkyProofIntern>,
+        cancel: &CancellationToken,
     ) -> Result<GeneratedBlockProof> {
-        let block_number = self.get_block_number();
-        info!("Proving block {block_number}");
+        self.decode()?.prove(runtime, previous, cancel).await
+    }
+}
 
-        let other_data = self.other_data;
-        let txs = self.block_trace.into_txn_proof_gen_ir(
-            &ProcessingMeta::new(resolve_code_hash_fn),
-            other_data.clone(),
-        )?;
+/// A block whose trace has been decoded into the inputs of its transaction
+/// proofs.
+#[derive(Debug)]
+pub struct DecodedProverInput {
+    pub block_number: U256,
+    pub generation_inputs: Vec<TxnProofGenIR>,
+}
 
-        let agg_proof = IndexedStream::from(txs)
+#[cfg(feature = "prove")]
+impl DecodedProverInput {
+    /// Proves the block, on top of the proof of its parent if given.
+    ///
+    /// Once `cancel` is cancelled, proving is abandoned at the next await
+    /// point and an error is returned. Operations already dispatched to
+    /// workers are not recalled.
+    pub async fn prove(
+        self,
+        runtime: &Runtime,
+        previous: Option<PlonkyProofIntern>,
+        cancel: &CancellationToken,
+    ) -> Result<GeneratedBlockProof> {
+        let block_number = self.block_number;
+        tokio::select! {
+            biased;
+            () = cancel.cancelled() => bail!("proving block {block_number} was cancelled"),
+            proof = self.run(runtime, previous) => proof,
+        }
+    }
+
+    #[cfg(not(feature = "test_only"))]
+    async fn run(
+        self,
+        runtime: &Runtime,
+        previous: Option<PlonkyProofIntern>,
+    ) -> Result<GeneratedBlockProof> {
+        let block_number = self.block_number;
+        info!("Proving block {block_number}");
+
+        let agg_proof = IndexedStream::from(self.generation_inputs)
             .map(&TxProof)
             .fold(&ops::AggProof)
             .run(runtime)
@@ -70,21 +133,15 @@ impl ProverInput {
     }
 
     #[cfg(feature = "test_only")]
-    pub async fn prove(
+    async fn run(
         self,
         runtime: &Runtime,
         _previous: Option<PlonkyProofIntern>,
     ) -> Result<GeneratedBlockProof> {
-        let block_number = self.get_block_number();
+        let block_number = self.block_number;
         info!("Testing witness generation for block {block_number}.");
 
-        let other_data = self.other_data;
-        let txs = self.block_trace.into_txn_proof_gen_ir(
-            &ProcessingMeta::new(resolve_code_hash_fn),
-            other_data.clone(),
-        )?;
-
-        IndexedStream::from(txs)
+        IndexedStream::from(self.generation_inputs)
             .map(&TxProof)
             .run(runtime)
             .await?
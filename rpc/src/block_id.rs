@@ -0,0 +1,137 @@
+//! Block identifiers accepted by the fetcher.
+use std::{fmt::Display, str::FromStr};
+
+use ethereum_types::H256;
+use serde_json::Value;
+
+/// A named block, resolved by the node at request time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockTag {
+    Earliest,
+    Latest,
+    Safe,
+    Finalized,
+}
+
+impl BlockTag {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BlockTag::Earliest => "earliest",
+            BlockTag::Latest => "latest",
+            BlockTag::Safe => "safe",
+            BlockTag::Finalized => "finalized",
+        }
+    }
+}
+
+/// Identifies a block by number, hash or tag.
+///
+/// Fetching by hash pins the request to a single fork, which makes it safe
+/// against reorgs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockId {
+    Number(u64),
+    Hash(H256),
+    Tag(BlockTag),
+}
+
+impl BlockId {
+    /// Returns the `*ByNumber` or `*ByHash` variant of the given RPC method
+    /// name (e.g., `eth_getBlockBy`) along with the matching parameter.
+    pub(crate) fn rpc_method_and_param(&self, method_prefix: &str) -> (String, Value) {
+        match self {
+            BlockId::Number(number) => (
+                format!("{method_prefix}Number"),
+                Value::String(format!("0x{number:x}")),
+            ),
+            BlockId::Hash(hash) => (
+                format!("{method_prefix}Hash"),
+                Value::String(format!("{hash:?}")),
+            ),
+            BlockId::Tag(tag) => (
+                format!("{method_prefix}Number"),
+                Value::String(tag.as_str().to_string()),
+            ),
+        }
+    }
+}
+
+/// Parses a decimal or `0x`-prefixed hexadecimal block number, a
+/// `0x`-prefixed 32-byte block hash, or a block tag.
+impl FromStr for BlockId {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let tag = match s {
+            "earliest" => Some(BlockTag::Earliest),
+            "latest" => Some(BlockTag::Latest),
+            "safe" => Some(BlockTag::Safe),
+            "finalized" => Some(BlockTag::Finalized),
+            _ => None,
+        };
+        if let Some(tag) = tag {
+            return Ok(BlockId::Tag(tag));
+        }
+
+        match s.strip_prefix("0x") {
+            Some(hex) if hex.len() == 64 => H256::from_str(hex)
+                .map(BlockId::Hash)
+                .map_err(|e| format!("invalid block hash {s:?}: {e}")),
+            Some(hex) => u64::from_str_radix(hex, 16)
+                .map(BlockId::Number)
+                .map_err(|e| format!("invalid block number {s:?}: {e}")),
+            None => s.parse().map(BlockId::Number).map_err(|_| {
+                format!(
+                    "expected a block number, a block hash, or one of earliest, latest, safe \
+                     and finalized; got {s:?}"
+                )
+            }),
+        }
+    }
+}
+
+impl From<u64> for BlockId {
+    fn from(number: u64) -> Self {
+        BlockId::Number(number)
+    }
+}
+
+impl From<H256> for BlockId {
+    fn from(hash: H256) -> Self {
+        BlockId::Hash(hash)
+    }
+}
+
+impl From<BlockTag> for BlockId {
+    fn from(tag: BlockTag) -> Self {
+        BlockId::Tag(tag)
+    }
+}
+
+impl Display for BlockId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BlockId::Number(number) => write!(f, "{number}"),
+            BlockId::Hash(hash) => write!(f, "{hash:?}"),
+            BlockId::Tag(tag) => write!(f, "{}", tag.as_str()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn it_parses_block_ids() {
+        assert_eq!("100".parse(), Ok(BlockId::Number(100)));
+        assert_eq!("0x64".parse(), Ok(BlockId::Number(100)));
+        assert_eq!("finalized".parse(), Ok(BlockId::Tag(BlockTag::Finalized)));
+        let hash = H256::repeat_byte(0xab);
+        assert_eq!(format!("{hash:?}").parse(), Ok(BlockId::Hash(hash)));
+
+        assert!("pending".parse::<BlockId>().is_err());
+        assert!("0xzz".parse::<BlockId>().is_err());
+        assert!(format!("{hash:?}0").parse::<BlockId>().is_err());
+    }
+}
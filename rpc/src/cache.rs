@@ -0,0 +1,80 @@
+//! On-disk cache of RPC responses.
+//!
+//! Once the block to fetch has been resolved to a hash, everything else the
+//! fetcher asks for is fixed by that hash: its trace, and the headers of its
+//! ancestors, even when looked up by number. Responses to those requests are
+//! cached under the chain id and the block hash, so fetching the same block
+//! again does not touch the node.
+use std::{fs, path::PathBuf};
+
+use anyhow::{Context, Result};
+use ethereum_types::{H256, U256};
+use serde_json::Value;
+
+use crate::trie::keccak;
+
+/// Block tags whose meaning changes over time, and which therefore make a
+/// request uncacheable.
+const MOVING_TAGS: [&str; 4] = ["latest", "safe", "finalized", "pending"];
+
+/// The block that the cached responses of a client belong to.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct CacheScope {
+    pub(crate) chain_id: U256,
+    pub(crate) block_hash: H256,
+}
+
+#[derive(Debug)]
+pub(crate) struct RpcCache {
+    dir: PathBuf,
+}
+
+impl RpcCache {
+    pub(crate) fn new(dir: PathBuf) -> Result<Self> {
+        fs::create_dir_all(&dir)
+            .with_context(|| format!("creating cache directory {}", dir.display()))?;
+        Ok(Self { dir })
+    }
+
+    /// Whether the response to a request depends only on its scope.
+    pub(crate) fn is_cacheable(params: &Value) -> bool {
+        match params {
+            Value::String(s) => !MOVING_TAGS.contains(&s.as_str()),
+            Value::Array(values) => values.iter().all(Self::is_cacheable),
+            Value::Object(values) => values.values().all(Self::is_cacheable),
+            _ => true,
+        }
+    }
+
+    /// Returns `<dir>/<chain id>/<block hash>/<method>-<hash of params>.json`.
+    fn path(&self, scope: CacheScope, method: &str, params: &Value) -> PathBuf {
+        let key = keccak(format!("{method}{params}").as_bytes());
+        self.dir
+            .join(scope.chain_id.to_string())
+            .join(format!("{:x}", scope.block_hash))
+            .join(format!("{method}-{}.json", hex::encode(&key[..16])))
+    }
+
+    pub(crate) fn get(&self, scope: CacheScope, method: &str, params: &Value) -> Option<Vec<u8>> {
+        fs::read(self.path(scope, method, params)).ok()
+    }
+
+    pub(crate) fn put(
+        &self,
+        scope: CacheScope,
+        method: &str,
+        params: &Value,
+        response: &[u8],
+    ) -> Result<()> {
+        let path = self.path(scope, method, params);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        // Write to a temporary file first, so that concurrent runs never read
+        // a partial response.
+        let tmp = path.with_extension(format!("tmp-{}", std::process::id()));
+        fs::write(&tmp, response)?;
+        fs::rename(&tmp, &path)?;
+        Ok(())
+    }
+}
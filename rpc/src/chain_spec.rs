@@ -0,0 +1,230 @@
+//! Chain parameters for networks that differ from Ethereum mainnet.
+//!
+//! A [`ChainSpec`] is read from a JSON file and lets operators of private or
+//! app-specific EVM chains describe their fork schedule and tracer setup
+//! without code changes. Well-known public chains can be selected by name
+//! with [`ChainSpec::resolve`].
+use std::{fs::File, path::Path};
+
+use anyhow::{bail, ensure, Context, Result};
+use prover::ProverInput;
+use serde::Deserialize;
+
+use crate::receipt::is_deposit_transaction;
+
+/// The number of previous block hashes expected by the prover.
+pub const PREV_HASHES_LEN: usize = 256;
+
+/// The tracer used by Jerigon nodes to produce the block witness.
+const DEFAULT_TRACER: &str = "zeroTracer";
+
+/// Chain parameters used to drive header parsing and block metadata
+/// construction.
+///
+/// All fields are optional in the JSON representation. Omitted fields fall
+/// back to the [`Default`] implementation, which matches the behaviour
+/// expected for a post-Shanghai chain.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct ChainSpec {
+    /// The chain id. If provided, the `eth_chainId` RPC call is skipped.
+    pub chain_id: Option<u64>,
+    /// The first block with an EIP-1559 base fee. `None` if the chain never
+    /// activated EIP-1559.
+    pub london_block: Option<u64>,
+    /// The first proof-of-stake block. `None` if the chain never switched
+    /// from proof-of-work.
+    pub merge_block: Option<u64>,
+    /// The timestamp from which blocks carry EIP-4895 withdrawals. `None` if
+    /// the chain never activated withdrawals.
+    pub shanghai_timestamp: Option<u64>,
+    /// The timestamp from which blocks carry an EIP-4788 parent beacon block
+    /// root. `None` if the chain never activated Cancun.
+    pub cancun_timestamp: Option<u64>,
+    /// The tracer requested from `debug_traceBlockByNumber`.
+    pub tracer: String,
+    /// The number of ancestor hashes exposed by the chain's `BLOCKHASH`
+    /// opcode. Older hashes are zeroed.
+    pub blockhash_window: usize,
+    /// The highest gas limit a block may declare. Blocks above it are
+    /// rejected. `None` if the chain imposes no bound beyond the header's.
+    pub max_gas_limit: Option<u64>,
+    /// Whether blocks may contain OP-stack deposit transactions (type
+    /// `0x7e`). Blocks containing them are rejected otherwise.
+    pub deposit_transactions: bool,
+}
+
+impl Default for ChainSpec {
+    fn default() -> Self {
+        Self {
+            chain_id: None,
+            london_block: Some(0),
+            merge_block: Some(0),
+            shanghai_timestamp: Some(0),
+            cancun_timestamp: None,
+            tracer: DEFAULT_TRACER.to_string(),
+            blockhash_window: PREV_HASHES_LEN,
+            max_gas_limit: None,
+            deposit_transactions: false,
+        }
+    }
+}
+
+/// The names accepted by [`ChainSpec::resolve`].
+pub const NAMED_CHAINS: [&str; 4] = ["mainnet", "sepolia", "holesky", "op-stack"];
+
+impl ChainSpec {
+    /// Returns the chain spec of a well-known public chain.
+    ///
+    /// OP-stack chains share no fork schedule or chain id, so `op-stack` only
+    /// allows deposit transactions on top of the [`Default`] spec.
+    pub fn named(name: &str) -> Option<Self> {
+        if name == "op-stack" {
+            return Some(Self {
+                deposit_transactions: true,
+                ..Self::default()
+            });
+        }
+        let (chain_id, london_block, merge_block, shanghai_timestamp, cancun_timestamp) = match name
+        {
+            "mainnet" => (1, 12_965_000, 15_537_394, 1_681_338_455, 1_710_338_135),
+            "sepolia" => (11_155_111, 0, 1_450_409, 1_677_557_088, 1_706_655_072),
+            "holesky" => (17_000, 0, 0, 1_696_000_704, 1_707_305_664),
+            _ => return None,
+        };
+        Some(Self {
+            chain_id: Some(chain_id),
+            london_block: Some(london_block),
+            merge_block: Some(merge_block),
+            shanghai_timestamp: Some(shanghai_timestamp),
+            cancun_timestamp: Some(cancun_timestamp),
+            ..Self::default()
+        })
+    }
+
+    /// Returns the chain spec of the named chain, or reads it from the JSON
+    /// file at `chain` if it is not a known name.
+    pub fn resolve(chain: &str) -> Result<Self> {
+        match Self::named(chain) {
+            Some(spec) => Ok(spec),
+            None if Path::new(chain).exists() => Self::from_file(chain),
+            None => bail!(
+                "{chain:?} is neither a known chain ({}) nor a chain spec file",
+                NAMED_CHAINS.join(", ")
+            ),
+        }
+    }
+
+    /// Reads and validates a chain spec from a JSON file.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let file =
+            File::open(path).with_context(|| format!("opening chain spec {}", path.display()))?;
+        let des = &mut serde_json::Deserializer::from_reader(&file);
+        let spec: Self = serde_path_to_error::deserialize(des)
+            .with_context(|| format!("deserializing chain spec {}", path.display()))?;
+        spec.validate()?;
+
+        Ok(spec)
+    }
+
+    pub(crate) fn validate(&self) -> Result<()> {
+        ensure!(
+            (1..=PREV_HASHES_LEN).contains(&self.blockhash_window),
+            "blockhash_window must be between 1 and {PREV_HASHES_LEN}, got {}",
+            self.blockhash_window
+        );
+        ensure!(!self.tracer.is_empty(), "tracer must not be empty");
+        ensure!(
+            self.max_gas_limit != Some(0),
+            "max_gas_limit must not be zero"
+        );
+
+        Ok(())
+    }
+
+    /// Whether the given block is expected to carry a base fee.
+    pub fn is_london(&self, block_number: u64) -> bool {
+        self.london_block.is_some_and(|b| block_number >= b)
+    }
+
+    /// Whether the given block is expected to carry `PREVRANDAO` randomness
+    /// in its mix hash rather than a proof-of-work difficulty.
+    pub fn is_merged(&self, block_number: u64) -> bool {
+        self.merge_block.is_some_and(|b| block_number >= b)
+    }
+
+    /// Whether a block with the given timestamp is expected to carry
+    /// withdrawals.
+    pub fn is_shanghai(&self, timestamp: u64) -> bool {
+        self.shanghai_timestamp.is_some_and(|t| timestamp >= t)
+    }
+
+    /// Whether a block with the given timestamp is expected to carry a parent
+    /// beacon block root.
+    pub fn is_cancun(&self, timestamp: u64) -> bool {
+        self.cancun_timestamp.is_some_and(|t| timestamp >= t)
+    }
+
+    /// Checks that the prover supports the forks active at the given block,
+    /// so that unsupported blocks are rejected before they are traced.
+    pub(crate) fn check_supported(&self, block_number: u64, timestamp: u64) -> Result<()> {
+        // Cancun adds blob transactions and the EIP-4788 beacon roots system
+        // contract, neither of which the prover implements.
+        ensure!(
+            !self.is_cancun(timestamp),
+            "block {block_number} is past the Cancun fork, which the prover does not support"
+        );
+        Ok(())
+    }
+
+    /// Checks that the block contains only transaction types the chain has.
+    pub(crate) fn check_transactions(&self, input: &ProverInput) -> Result<()> {
+        if self.deposit_transactions {
+            return Ok(());
+        }
+        let deposit = input
+            .block_trace
+            .txn_info
+            .iter()
+            .position(|txn| is_deposit_transaction(&txn.meta.new_txn_trie_node_byte));
+        if let Some(i) = deposit {
+            bail!(
+                "transaction {i} of block {} is an OP-stack deposit transaction, which needs \
+                 --chain op-stack or a chain spec with deposit_transactions",
+                input.get_block_number()
+            );
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn it_resolves_named_chains() {
+        let mainnet = ChainSpec::resolve("mainnet").unwrap();
+        assert_eq!(mainnet.chain_id, Some(1));
+        assert!(!mainnet.is_london(12_964_999));
+        assert!(mainnet.is_london(12_965_000));
+        assert!(mainnet.check_supported(19_000_000, 1_705_000_000).is_ok());
+        assert!(mainnet.check_supported(19_500_000, 1_710_338_135).is_err());
+
+        for name in NAMED_CHAINS {
+            assert!(ChainSpec::named(name).unwrap().validate().is_ok());
+        }
+        assert!(ChainSpec::resolve("no-such-chain").is_err());
+        let no_gas = ChainSpec {
+            max_gas_limit: Some(0),
+            ..ChainSpec::default()
+        };
+        assert!(no_gas.validate().is_err());
+
+        let op_stack = ChainSpec::resolve("op-stack").unwrap();
+        assert!(op_stack.deposit_transactions);
+        assert_eq!(op_stack.chain_id, None);
+        assert!(!mainnet.deposit_transactions);
+    }
+}
@@ -1,4 +1,8 @@
-use clap::{Parser, Subcommand, ValueHint};
+use std::{ops::Range, path::PathBuf};
+
+use clap::{ArgGroup, Parser, Subcommand, ValueHint};
+use common::parsing::parse_range;
+use rpc::{block_id::BlockId, client::RpcArgs, header_defaults::HeaderDefaults};
 
 #[derive(Parser)]
 pub(crate) struct Cli {
@@ -7,17 +11,112 @@ pub(crate) struct Cli {
 }
 
 #[derive(Subcommand)]
+#[allow(clippy::large_enum_variant)]
 pub(crate) enum Commands {
     /// Fetch and generate prover input from the RPC endpoint
+    #[command(group(ArgGroup::new("blocks").args(["block_interval", "follow"])))]
     Fetch {
-        /// The RPC URL
-        #[arg(short = 'u', long, value_hint = ValueHint::Url)]
-        rpc_url: String,
-        /// The block number
+        #[command(flatten)]
+        rpc: RpcArgs,
+        /// The block: a number, a hash, or one of `latest`, `safe`,
+        /// `finalized` and `earliest`
+        #[arg(
+            short,
+            long,
+            visible_alias = "block-number",
+            required_unless_present_any = ["block_interval", "follow"]
+        )]
+        block: Option<BlockId>,
+        /// Fetch every block in the range `START..END` (end excluded) instead
+        /// of a single block
+        #[arg(long, conflicts_with_all = ["block", "compare_with"], value_parser = parse_range::<u64>)]
+        block_interval: Option<Range<u64>>,
+        /// Keep fetching new blocks as the chain grows, starting at the block
+        /// if one is given, or else at the latest confirmed block
+        #[arg(long, conflicts_with_all = ["block_interval", "compare_with", "pre_state"])]
+        follow: bool,
+        /// How many blocks a followed block must be behind the head before it
+        /// is fetched
+        #[arg(long, default_value_t = 2, requires = "follow")]
+        confirmations: u64,
+        /// How often, in seconds, the head is polled when following
+        #[arg(long, default_value_t = 4, requires = "follow")]
+        poll_interval: u64,
+        /// Write the prover input of each block of the interval, or of each
+        /// followed block, to `<OUTPUT_DIR>/block-<NUMBER>.json` instead of
+        /// stdout
+        #[arg(long, requires = "blocks", value_hint = ValueHint::DirPath)]
+        output_dir: Option<PathBuf>,
+        /// Record the last block of the interval written in this file, and
+        /// resume after it when the file exists
+        #[arg(long, requires = "block_interval", value_hint = ValueHint::FilePath)]
+        cursor_file: Option<PathBuf>,
+        /// List every block written to the output directory, with the path
+        /// and SHA-256 hash of its prover input, and the block that failed,
+        /// in this JSON file
+        #[arg(long, requires = "output_dir", value_hint = ValueHint::FilePath)]
+        manifest: Option<PathBuf>,
+        /// The checkpoint block, in the same forms as the block
+        #[arg(
+            short,
+            long,
+            visible_alias = "checkpoint-block-number",
+            default_value = "0"
+        )]
+        checkpoint: BlockId,
+        /// The chain to fetch from: `mainnet`, `sepolia`, `holesky`,
+        /// `op-stack`, or a JSON chain spec describing the fork schedule of
+        /// another chain.
+        #[arg(long, visible_alias = "chain-spec", value_name = "NAME|PATH")]
+        chain: Option<String>,
+        #[command(flatten)]
+        header_defaults: HeaderDefaults,
+        /// Compare the fetched prover input against this reference, failing
+        /// on any difference
+        #[arg(long, value_hint = ValueHint::FilePath)]
+        compare_with: Option<PathBuf>,
+        /// Use the trie pre-images of this pre-state, as written by `rpc
+        /// pre-state`, instead of the node's witness, keeping only the
+        /// node's transaction traces
+        #[arg(long, conflicts_with = "block_interval", value_hint = ValueHint::FilePath)]
+        pre_state: Option<PathBuf>,
+        /// Override fields of the fetched block data with the ones in this
+        /// JSON file, marking the prover input as synthetic
+        #[arg(long, conflicts_with_all = ["block_interval", "follow"], value_hint = ValueHint::FilePath)]
+        override_block_data: Option<PathBuf>,
+    },
+    /// Fetch the state a block executes on, without its transaction traces
+    PreState {
+        #[command(flatten)]
+        rpc: RpcArgs,
+        /// The block: a number, a hash, or one of `latest`, `safe`,
+        /// `finalized` and `earliest`
+        #[arg(short, long, visible_alias = "block-number")]
+        block: BlockId,
+        /// The chain to fetch from: `mainnet`, `sepolia`, `holesky`,
+        /// `op-stack`, or a JSON chain spec describing the fork schedule of
+        /// another chain.
+        #[arg(long, visible_alias = "chain-spec", value_name = "NAME|PATH")]
+        chain: Option<String>,
+    },
+    /// Compute the hash-chain commitment over a range of blocks from their
+    /// headers
+    CommitRange {
+        #[command(flatten)]
+        rpc: RpcArgs,
+        /// The first block of the range
         #[arg(short, long)]
-        block_number: u64,
-        /// The checkpoint block number
-        #[arg(short, long, default_value_t = 0)]
-        checkpoint_block_number: u64,
+        start_block: u64,
+        /// The last block of the range
+        #[arg(short, long)]
+        end_block: u64,
+    },
+    /// Check a prover input for structural problems, reporting all of them,
+    /// and check that its witness decodes
+    #[command(visible_alias = "lint-witness")]
+    Lint {
+        /// The prover input to check
+        #[arg(short, long, value_hint = ValueHint::FilePath)]
+        input: PathBuf,
     },
 }
@@ -0,0 +1,1251 @@
+//! JSON-RPC transport shared by every request made during a fetch.
+use std::{
+    collections::HashMap,
+    fs,
+    future::Future,
+    net::{IpAddr, SocketAddr},
+    num::{NonZeroU32, NonZeroUsize},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use bytes::Bytes;
+use clap::{Args, ValueEnum, ValueHint};
+use ethereum_types::{H256, U256};
+use reqwest::Url;
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::{json, Value};
+use tracing::{debug, info, warn};
+
+use crate::{
+    cache::{CacheScope, RpcCache},
+    chain_spec::ChainSpec,
+    dns::CachingResolver,
+    error::RpcError,
+    health::{Availability, EndpointHealth, EndpointStatus, QuarantinePolicy},
+    jwt::JwtSecret,
+    metrics::RpcMetrics,
+    profile::{load_profile, ProfileAuth, RpcProfile, TransportProfile},
+    rate_limit::RateLimiter,
+};
+
+/// Connection options for the RPC node.
+#[derive(Args, Debug, Clone)]
+pub struct RpcArgs {
+    /// The RPC URL. Repeat to add fallback endpoints, which are tried in
+    /// order when the active one fails
+    #[arg(short = 'u', long, value_hint = ValueHint::Url, required_unless_present = "rpc_profile")]
+    pub rpc_url: Vec<Url>,
+    /// Use the named endpoint profile from the RPC profiles file instead of
+    /// `--rpc-url`.
+    #[arg(long, conflicts_with = "rpc_url", requires = "rpc_profiles")]
+    pub rpc_profile: Option<String>,
+    /// A JSON file of named RPC endpoint profiles.
+    #[arg(long, env = "RPC_PROFILES", value_hint = ValueHint::FilePath)]
+    pub rpc_profiles: Option<PathBuf>,
+    /// Record every JSON-RPC request and response to this directory.
+    #[arg(long, visible_alias = "dump-rpc", value_hint = ValueHint::DirPath)]
+    pub capture_rpc: Option<PathBuf>,
+    /// Cache the responses pinned to the fetched block in this directory, so
+    /// that fetching it again does not hit the node.
+    #[arg(long, env = "RPC_CACHE_DIR", value_hint = ValueHint::DirPath)]
+    pub cache_dir: Option<PathBuf>,
+    /// Record the number, latency and failures of RPC calls per method, and
+    /// print a summary after fetching.
+    #[arg(long, env = "RPC_METRICS")]
+    pub rpc_metrics: bool,
+    /// The timeout, in seconds, of RPC requests without a method-specific
+    /// timeout.
+    #[arg(long, env = "RPC_TIMEOUT", default_value_t = 30)]
+    pub rpc_timeout: u64,
+    /// The timeout of a single RPC method, as `METHOD=SECONDS`. May be
+    /// repeated. Block tracing methods default to 600 seconds.
+    #[arg(
+        long,
+        env = "RPC_METHOD_TIMEOUTS",
+        value_delimiter = ',',
+        value_parser = parse_method_timeout
+    )]
+    pub rpc_method_timeout: Vec<(String, u64)>,
+    /// The maximum number of RPC requests per second, across all concurrent
+    /// requests of a fetch.
+    #[arg(long, env = "RPC_MAX_RPS")]
+    pub max_rps: Option<NonZeroU32>,
+    /// The maximum number of block headers fetched concurrently when
+    /// collecting the hashes of previous blocks.
+    #[arg(long, env = "RPC_PREV_HASH_CONCURRENCY", default_value = "16")]
+    pub prev_hash_concurrency: NonZeroUsize,
+    /// The number of consecutive failures after which an RPC endpoint is
+    /// quarantined.
+    #[arg(long, env = "RPC_QUARANTINE_THRESHOLD", default_value = "3")]
+    pub rpc_quarantine_threshold: NonZeroU32,
+    /// How long, in seconds, a quarantined RPC endpoint is skipped before it
+    /// is probed.
+    #[arg(long, env = "RPC_QUARANTINE_COOLDOWN", default_value_t = 30)]
+    pub rpc_quarantine_cooldown: u64,
+    /// Send the requests for previous block hashes as JSON-RPC batches of up
+    /// to this many requests. Disabled by default, as not all nodes accept
+    /// batches.
+    #[arg(long, env = "RPC_BATCH_SIZE")]
+    pub rpc_batch_size: Option<NonZeroUsize>,
+    /// The maximum size, in bytes, of an RPC response. Larger responses are
+    /// abandoned as they arrive, so that a misbehaving node cannot exhaust
+    /// the fetcher's memory.
+    #[arg(long, env = "RPC_MAX_RESPONSE_SIZE", default_value_t = 1 << 30)]
+    pub rpc_max_response_size: u64,
+    /// A bearer token to send in the `Authorization` header of every RPC
+    /// request.
+    #[arg(long, env = "RPC_AUTH_TOKEN", hide_env_values = true)]
+    pub rpc_auth_token: Option<String>,
+    /// Credentials for HTTP basic authentication of every RPC request, as
+    /// `USER:PASSWORD`.
+    #[arg(
+        long,
+        env = "RPC_AUTH_BASIC",
+        hide_env_values = true,
+        conflicts_with = "rpc_auth_token"
+    )]
+    pub rpc_auth_basic: Option<String>,
+    /// A file holding the hex-encoded secret of a JWT-authenticated RPC port,
+    /// as used by the engine API.
+    #[arg(
+        long,
+        env = "RPC_JWT_SECRET",
+        value_hint = ValueHint::FilePath,
+        conflicts_with_all = ["rpc_auth_token", "rpc_auth_basic"]
+    )]
+    pub jwt_secret: Option<PathBuf>,
+    /// Send RPC requests through this `http://`, `https://`, `socks5://` or
+    /// `socks5h://` proxy, instead of the one set by the `HTTPS_PROXY`,
+    /// `HTTP_PROXY` or `ALL_PROXY` environment variables.
+    #[arg(long, env = "RPC_PROXY_URL", hide_env_values = true, value_hint = ValueHint::Url)]
+    pub proxy_url: Option<Url>,
+    #[command(flatten)]
+    pub pool: PoolArgs,
+}
+
+/// The help heading for the connection pool arguments.
+const POOL_HEADING: &str = "Connection pool";
+
+/// Tuning of the HTTP connections to the RPC node, for fetches that issue
+/// many concurrent requests.
+#[derive(Args, Debug, Clone)]
+pub struct PoolArgs {
+    /// The maximum number of idle connections kept open per endpoint.
+    /// Unlimited by default.
+    #[arg(long, help_heading = POOL_HEADING)]
+    pub rpc_pool_max_idle: Option<usize>,
+    /// How long, in seconds, idle connections are kept open.
+    #[arg(long, help_heading = POOL_HEADING, default_value_t = 90)]
+    pub rpc_pool_idle_timeout: u64,
+    /// The interval, in seconds, of TCP keep-alive probes on open
+    /// connections. Disabled by default.
+    #[arg(long, help_heading = POOL_HEADING)]
+    pub rpc_tcp_keepalive: Option<u64>,
+    /// The HTTP version to speak to the node. `auto`, the default, negotiates
+    /// HTTP/2 over TLS and uses HTTP/1.1 otherwise.
+    #[arg(long, help_heading = POOL_HEADING, value_enum)]
+    pub rpc_http_version: Option<HttpVersion>,
+    /// Cache the addresses of RPC endpoints for this many seconds. When
+    /// resolving fails, the last addresses are used regardless of their age.
+    #[arg(long, help_heading = POOL_HEADING)]
+    pub rpc_dns_cache_ttl: Option<u64>,
+    /// The IP version used to connect to the node.
+    #[arg(long, help_heading = POOL_HEADING, value_enum, default_value_t = IpVersion::Any)]
+    pub rpc_ip_version: IpVersion,
+    /// The local address to connect from, to pick a network interface.
+    #[arg(long, help_heading = POOL_HEADING)]
+    pub rpc_local_address: Option<IpAddr>,
+}
+
+#[derive(ValueEnum, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum HttpVersion {
+    Auto,
+    /// Only use HTTP/1.1.
+    Http1,
+    /// Use HTTP/2 without negotiation, including over plain TCP.
+    Http2,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IpVersion {
+    /// Race IPv6 and IPv4 connections when both are available.
+    Any,
+    V4,
+    V6,
+}
+
+impl IpVersion {
+    pub(crate) fn accepts(self, addr: &SocketAddr) -> bool {
+        match self {
+            IpVersion::Any => true,
+            IpVersion::V4 => addr.is_ipv4(),
+            IpVersion::V6 => addr.is_ipv6(),
+        }
+    }
+
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            IpVersion::Any => "IP",
+            IpVersion::V4 => "IPv4",
+            IpVersion::V6 => "IPv6",
+        }
+    }
+}
+
+impl PoolArgs {
+    fn http_client(
+        &self,
+        profile: &TransportProfile,
+        proxy_url: Option<&Url>,
+    ) -> Result<reqwest::Client> {
+        let mut builder = reqwest::Client::builder()
+            .pool_idle_timeout(Duration::from_secs(self.rpc_pool_idle_timeout))
+            .tcp_keepalive(self.rpc_tcp_keepalive.map(Duration::from_secs))
+            .local_address(self.rpc_local_address);
+        if let Some(max_idle) = self.rpc_pool_max_idle {
+            builder = builder.pool_max_idle_per_host(max_idle);
+        }
+        let http_version = self.rpc_http_version.or(profile.http_version);
+        builder = match http_version.unwrap_or(HttpVersion::Auto) {
+            HttpVersion::Auto => builder,
+            HttpVersion::Http1 => builder.http1_only(),
+            HttpVersion::Http2 => builder.http2_prior_knowledge(),
+        };
+        if self.rpc_dns_cache_ttl.is_some() || self.rpc_ip_version != IpVersion::Any {
+            let ttl = Duration::from_secs(self.rpc_dns_cache_ttl.unwrap_or_default());
+            builder =
+                builder.dns_resolver(Arc::new(CachingResolver::new(ttl, self.rpc_ip_version)));
+        }
+        if let Some(url) = proxy_url.or(profile.proxy_url.as_ref()) {
+            let proxy = reqwest::Proxy::all(url.clone()).context("configuring the RPC proxy")?;
+            builder = builder.proxy(proxy);
+        }
+        builder.build().context("building the HTTP client")
+    }
+}
+
+/// Methods that legitimately take much longer than the default timeout, with
+/// their default timeouts in seconds.
+const SLOW_METHODS: [(&str, u64); 2] = [
+    ("debug_traceBlockByNumber", 600),
+    ("debug_traceBlockByHash", 600),
+];
+
+fn parse_method_timeout(s: &str) -> Result<(String, u64), String> {
+    let (method, seconds) = s
+        .split_once('=')
+        .ok_or_else(|| format!("expected METHOD=SECONDS, got {s:?}"))?;
+    let seconds = seconds
+        .parse()
+        .map_err(|e| format!("invalid timeout {seconds:?}: {e}"))?;
+    Ok((method.to_string(), seconds))
+}
+
+/// Credentials attached to every request.
+#[derive(Clone)]
+enum RpcAuth {
+    Bearer(String),
+    Basic {
+        username: String,
+        password: Option<String>,
+    },
+    /// A freshly signed JWT, as expected by authenticated node ports.
+    Jwt(JwtSecret),
+}
+
+impl RpcAuth {
+    /// Parses `USER:PASSWORD`, or just `USER`, into basic credentials.
+    fn basic(credentials: &str) -> Self {
+        let (username, password) = match credentials.split_once(':') {
+            Some((username, password)) => (username, Some(password.to_string())),
+            None => (credentials, None),
+        };
+        RpcAuth::Basic {
+            username: username.to_string(),
+            password,
+        }
+    }
+
+    fn apply(&self, request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        match self {
+            RpcAuth::Bearer(token) => request.bearer_auth(token),
+            RpcAuth::Basic { username, password } => {
+                request.basic_auth(username, password.as_ref())
+            }
+            RpcAuth::Jwt(secret) => request.bearer_auth(secret.token()),
+        }
+    }
+}
+
+impl std::fmt::Debug for RpcAuth {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RpcAuth::Bearer(_) => f.write_str("Bearer(..)"),
+            RpcAuth::Basic { username, .. } => write!(f, "Basic({username}:..)"),
+            RpcAuth::Jwt(secret) => write!(f, "Jwt({secret:?})"),
+        }
+    }
+}
+
+/// Request timeouts, per method.
+#[derive(Debug)]
+struct RpcTimeouts {
+    default: Duration,
+    methods: HashMap<String, Duration>,
+}
+
+impl RpcTimeouts {
+    fn for_method(&self, method: &str) -> Duration {
+        self.methods.get(method).copied().unwrap_or(self.default)
+    }
+}
+
+impl RpcArgs {
+    /// Resolves the endpoint settings, either from `--rpc-url` or from the
+    /// selected profile.
+    pub fn profile(&self) -> Result<RpcProfile> {
+        match (
+            self.rpc_url.split_first(),
+            &self.rpc_profile,
+            &self.rpc_profiles,
+        ) {
+            (Some((url, fallback_urls)), _, _) => {
+                Ok(RpcProfile::new(url.clone(), fallback_urls.to_vec()))
+            }
+            (None, Some(name), Some(path)) => load_profile(path, name),
+            _ => Err(anyhow!(
+                "either --rpc-url or --rpc-profile with --rpc-profiles is required"
+            )),
+        }
+    }
+
+    /// Resolves the chain spec, from `chain` (the `--chain` flag of commands
+    /// that take one) if given, or else from the profile, falling back to the
+    /// default.
+    pub fn chain_spec(&self, chain: Option<&str>, profile: &RpcProfile) -> Result<ChainSpec> {
+        match chain {
+            Some(chain) => ChainSpec::resolve(chain),
+            None => Ok(profile.chain_spec.clone().unwrap_or_default()),
+        }
+    }
+
+    /// Resolves the credentials, from the flags if any are given, or else
+    /// from the profile.
+    fn auth(&self, profile: &RpcProfile) -> Result<Option<RpcAuth>> {
+        if let Some(path) = &self.jwt_secret {
+            return Ok(Some(RpcAuth::Jwt(JwtSecret::from_file(path)?)));
+        }
+        if let Some(token) = &self.rpc_auth_token {
+            return Ok(Some(RpcAuth::Bearer(token.clone())));
+        }
+        if let Some(credentials) = &self.rpc_auth_basic {
+            return Ok(Some(RpcAuth::basic(credentials)));
+        }
+        Ok(match &profile.auth {
+            Some(ProfileAuth::JwtSecret(path)) => Some(RpcAuth::Jwt(JwtSecret::from_file(path)?)),
+            Some(ProfileAuth::Bearer(token)) => Some(RpcAuth::Bearer(token.clone())),
+            Some(ProfileAuth::Basic(credentials)) => Some(RpcAuth::basic(credentials)),
+            None => None,
+        })
+    }
+
+    pub fn client(&self, profile: &RpcProfile) -> Result<RpcClient> {
+        let capture = self
+            .capture_rpc
+            .clone()
+            .map(RpcCapture::new)
+            .transpose()?
+            .map(Arc::new);
+
+        let endpoints = std::iter::once(&profile.url)
+            .chain(&profile.fallback_urls)
+            .cloned()
+            .collect();
+
+        let timeouts = RpcTimeouts {
+            default: Duration::from_secs(self.rpc_timeout),
+            methods: SLOW_METHODS
+                .iter()
+                .map(|&(method, seconds)| (method.to_string(), seconds))
+                .chain(self.rpc_method_timeout.iter().cloned())
+                .map(|(method, seconds)| (method, Duration::from_secs(seconds)))
+                .collect(),
+        };
+        let quarantine = QuarantinePolicy {
+            threshold: self.rpc_quarantine_threshold.get(),
+            cooldown: Duration::from_secs(self.rpc_quarantine_cooldown),
+        };
+
+        Ok(RpcClient {
+            http: self
+                .pool
+                .http_client(&profile.transport, self.proxy_url.as_ref())?,
+            endpoints,
+            active: Default::default(),
+            health: std::iter::repeat_with(|| EndpointHealth::new(quarantine))
+                .take(profile.fallback_urls.len() + 1)
+                .collect(),
+            timeouts: Arc::new(timeouts),
+            rate_limiter: self
+                .max_rps
+                .or(profile.max_rps)
+                .map(|rate| Arc::new(RateLimiter::per_second(rate))),
+            batch_size: self.rpc_batch_size.or(profile.batch_size),
+            max_response_size: self.rpc_max_response_size,
+            auth: self.auth(profile)?,
+            cache: self
+                .cache_dir
+                .clone()
+                .map(RpcCache::new)
+                .transpose()?
+                .map(Arc::new),
+            cache_scope: None,
+            staged: None,
+            metrics: self.rpc_metrics.then(Default::default),
+            capture,
+        })
+    }
+}
+
+/// Writes raw request/response pairs to a directory.
+///
+/// Each call produces `<seq>-<method>.request.json` and
+/// `<seq>-<method>.response.json`, where `seq` orders calls by the time their
+/// responses arrived. Responses are written verbatim, so they can be fed back
+/// to a parser even when they failed to deserialize.
+#[derive(Debug)]
+struct RpcCapture {
+    dir: PathBuf,
+    seq: AtomicUsize,
+}
+
+impl RpcCapture {
+    fn new(dir: PathBuf) -> Result<Self> {
+        fs::create_dir_all(&dir)
+            .with_context(|| format!("creating capture directory {}", dir.display()))?;
+        Ok(Self {
+            dir,
+            seq: AtomicUsize::new(0),
+        })
+    }
+
+    fn record(&self, method: &str, request: &Value, response: &[u8]) -> Result<()> {
+        let seq = self.seq.fetch_add(1, Ordering::Relaxed);
+        let prefix = self.dir.join(format!("{seq:06}-{method}"));
+        fs::write(
+            prefix.with_extension("request.json"),
+            serde_json::to_vec(request)?,
+        )?;
+        fs::write(prefix.with_extension("response.json"), response)?;
+        Ok(())
+    }
+}
+
+/// The outcome of a failed attempt to call an endpoint.
+enum AttemptError {
+    /// The endpoint is unreachable or misbehaving; another one may succeed.
+    Endpoint(anyhow::Error),
+    /// The endpoint answered, but the answer could not be used.
+    Response(anyhow::Error),
+    /// The endpoint rejected the request itself, as any other endpoint would.
+    Rejected(anyhow::Error),
+    /// The endpoint answered, but handling the answer failed on our side.
+    Local(anyhow::Error),
+}
+
+fn decode_error<E: std::error::Error + Send + Sync + 'static>(
+    method: &str,
+    source: E,
+) -> anyhow::Error {
+    RpcError::Decode {
+        method: method.to_owned(),
+        source: Box::new(source),
+    }
+    .into()
+}
+
+impl AttemptError {
+    fn into_inner(self) -> anyhow::Error {
+        match self {
+            AttemptError::Endpoint(e)
+            | AttemptError::Response(e)
+            | AttemptError::Rejected(e)
+            | AttemptError::Local(e) => e,
+        }
+    }
+}
+
+/// The JSON-RPC error code of requests for methods the node does not serve.
+const METHOD_NOT_FOUND: i64 = -32601;
+
+/// JSON-RPC error codes of malformed requests.
+const INVALID_REQUEST: i64 = -32600;
+const INVALID_PARAMS: i64 = -32602;
+
+/// The error code of calls that reverted, as returned by geth-based nodes.
+/// Other nodes only say so in the message.
+const EXECUTION_REVERTED: i64 = 3;
+
+/// A JSON-RPC error object.
+#[derive(Deserialize, Debug)]
+struct JsonRpcError {
+    code: i64,
+    message: String,
+}
+
+#[derive(Deserialize)]
+struct JsonRpcErrorResponse {
+    error: JsonRpcError,
+}
+
+impl JsonRpcErrorResponse {
+    /// Classifies the error: those caused by the request are returned by
+    /// every endpoint alike, so only the others fail over.
+    fn into_attempt_error(self, method: &str) -> AttemptError {
+        let method = method.to_owned();
+        let JsonRpcError { code, message } = self.error;
+        let rejected = matches!(
+            code,
+            METHOD_NOT_FOUND | INVALID_REQUEST | INVALID_PARAMS | EXECUTION_REVERTED
+        ) || message.starts_with("execution reverted");
+        let error = match code {
+            METHOD_NOT_FOUND => RpcError::MethodNotFound { method },
+            code => RpcError::Node {
+                method,
+                code,
+                message,
+            },
+        }
+        .into();
+        if rejected {
+            AttemptError::Rejected(error)
+        } else {
+            AttemptError::Endpoint(error)
+        }
+    }
+}
+
+/// A JSON-RPC client for a node, with optional fallback endpoints.
+///
+/// Requests go to the active endpoint. When it is unreachable, responds with
+/// an HTTP error or returns a JSON-RPC error, the next endpoint is tried and
+/// becomes the active one if it succeeds. Errors that any endpoint would
+/// return, such as for an unknown method, invalid parameters or a reverted
+/// call, are returned right away.
+///
+/// Cheap to clone; clones share the underlying connection pool and the active
+/// endpoint.
+#[derive(Debug, Clone)]
+pub struct RpcClient {
+    http: reqwest::Client,
+    endpoints: Arc<[Url]>,
+    active: Arc<AtomicUsize>,
+    health: Arc<[EndpointHealth]>,
+    timeouts: Arc<RpcTimeouts>,
+    rate_limiter: Option<Arc<RateLimiter>>,
+    batch_size: Option<NonZeroUsize>,
+    max_response_size: u64,
+    auth: Option<RpcAuth>,
+    cache: Option<Arc<RpcCache>>,
+    cache_scope: Option<CacheScope>,
+    staged: Option<Arc<Mutex<Vec<StagedResponse>>>>,
+    metrics: Option<Arc<RpcMetrics>>,
+    capture: Option<Arc<RpcCapture>>,
+}
+
+/// A response held back from the cache by a staged client.
+#[derive(Debug)]
+struct StagedResponse {
+    scope: CacheScope,
+    method: String,
+    params: Value,
+    bytes: Vec<u8>,
+}
+
+impl RpcClient {
+    /// Sends a JSON-RPC request and deserializes the full response envelope
+    /// into `T`.
+    pub(crate) async fn request<T: DeserializeOwned>(
+        &self,
+        method: &str,
+        params: Value,
+    ) -> Result<T> {
+        let request = json!({
+            "jsonrpc": "2.0",
+            "method": method,
+            "params": params,
+            "id": 1,
+        });
+
+        let cache = self.cache_for(&request["params"]);
+        if let Some(response) = cache
+            .and_then(|(cache, scope)| self.read_cache(cache, scope, method, &request["params"]))
+        {
+            return Ok(response);
+        }
+
+        let request = &request;
+        self.with_failover(method, |url| async move {
+            let bytes = self.post(&url, method, request, 1).await?;
+            let des = &mut serde_json::Deserializer::from_slice(&bytes);
+            let response = serde_path_to_error::deserialize(des).map_err(|e| {
+                // Only look for an error object once the response failed to
+                // parse, to avoid scanning large successful responses twice.
+                match serde_json::from_slice::<JsonRpcErrorResponse>(&bytes) {
+                    Ok(response) => response.into_attempt_error(method),
+                    Err(_) => AttemptError::Response(decode_error(method, e)),
+                }
+            })?;
+            if let Some((cache, scope)) = cache {
+                self.write_cache(cache, scope, method, &request["params"], &bytes);
+            }
+            Ok(response)
+        })
+        .await
+    }
+
+    /// Returns a client whose responses to requests that depend only on the
+    /// given block are cached, if caching is enabled.
+    pub(crate) fn scoped(&self, chain_id: U256, block_hash: H256) -> RpcClient {
+        RpcClient {
+            cache_scope: Some(CacheScope {
+                chain_id,
+                block_hash,
+            }),
+            ..self.clone()
+        }
+    }
+
+    /// Returns a client that holds back the responses it would cache until
+    /// [`RpcClient::commit_staged`] is called on it.
+    ///
+    /// This is for responses that are pinned to the scope only once checked,
+    /// such as ancestors looked up by number, which belong to a different
+    /// fork if the chain reorganized during the fetch.
+    pub(crate) fn staged(&self) -> RpcClient {
+        RpcClient {
+            staged: Some(Default::default()),
+            ..self.clone()
+        }
+    }
+
+    /// Caches the responses held back by a client returned by
+    /// [`RpcClient::staged`].
+    pub(crate) fn commit_staged(&self) {
+        let (Some(cache), Some(staged)) = (&self.cache, &self.staged) else {
+            return;
+        };
+        let staged = std::mem::take(&mut *staged.lock().unwrap());
+        for response in staged {
+            let StagedResponse {
+                scope,
+                method,
+                params,
+                bytes,
+            } = response;
+            if let Err(e) = cache.put(scope, &method, &params, &bytes) {
+                warn!("Failed to cache {method}: {e:#}");
+            }
+        }
+    }
+
+    fn write_cache(
+        &self,
+        cache: &RpcCache,
+        scope: CacheScope,
+        method: &str,
+        params: &Value,
+        bytes: &[u8],
+    ) {
+        match &self.staged {
+            Some(staged) => staged.lock().unwrap().push(StagedResponse {
+                scope,
+                method: method.to_owned(),
+                params: params.clone(),
+                bytes: bytes.to_vec(),
+            }),
+            None => {
+                if let Err(e) = cache.put(scope, method, params, bytes) {
+                    warn!("Failed to cache {method}: {e:#}");
+                }
+            }
+        }
+    }
+
+    fn cache_for(&self, params: &Value) -> Option<(&RpcCache, CacheScope)> {
+        match (&self.cache, self.cache_scope) {
+            (Some(cache), Some(scope)) if RpcCache::is_cacheable(params) => Some((cache, scope)),
+            _ => None,
+        }
+    }
+
+    /// Reads a response from the cache, ignoring entries that no longer
+    /// deserialize.
+    fn read_cache<T: DeserializeOwned>(
+        &self,
+        cache: &RpcCache,
+        scope: CacheScope,
+        method: &str,
+        params: &Value,
+    ) -> Option<T> {
+        let bytes = cache.get(scope, method, params)?;
+        match serde_json::from_slice(&bytes) {
+            Ok(response) => {
+                debug!("Serving {method} from the cache");
+                Some(response)
+            }
+            Err(e) => {
+                warn!("Ignoring unreadable cached {method}: {e}");
+                None
+            }
+        }
+    }
+
+    /// The maximum number of requests to send in a single JSON-RPC batch, if
+    /// batching is enabled.
+    pub(crate) fn batch_size(&self) -> Option<NonZeroUsize> {
+        self.batch_size
+    }
+
+    /// Sends a JSON-RPC request for each of `params` in a single batch, and
+    /// deserializes the full response envelopes into `T`, in the order of
+    /// `params`.
+    pub(crate) async fn batch_request<T: DeserializeOwned>(
+        &self,
+        method: &str,
+        params: Vec<Value>,
+    ) -> Result<Vec<T>> {
+        let len = params.len();
+        let request = Value::Array(
+            params
+                .iter()
+                .enumerate()
+                .map(|(id, params)| {
+                    json!({
+                        "jsonrpc": "2.0",
+                        "method": method,
+                        "params": params,
+                        "id": id,
+                    })
+                })
+                .collect(),
+        );
+
+        // The whole batch is cached as a single entry.
+        let params = Value::Array(params);
+        let cache = self.cache_for(&params);
+        if let Some(responses) =
+            cache.and_then(|(cache, scope)| self.read_cache(cache, scope, method, &params))
+        {
+            return Ok(responses);
+        }
+
+        let request = &request;
+        let params = &params;
+        self.with_failover(method, |url| async move {
+            let bytes = self.post(&url, method, request, len).await?;
+            let responses: Vec<Value> = serde_json::from_slice(&bytes).map_err(|e| {
+                // Nodes without batch support answer with a single error, which
+                // other endpoints may not, whatever its code.
+                match serde_json::from_slice::<JsonRpcErrorResponse>(&bytes) {
+                    Ok(response) => {
+                        AttemptError::Endpoint(response.into_attempt_error(method).into_inner())
+                    }
+                    Err(_) => AttemptError::Response(decode_error(method, e)),
+                }
+            })?;
+            if responses.len() != len {
+                return Err(AttemptError::Endpoint(anyhow!(
+                    "{method} batch returned {} responses to {len} requests",
+                    responses.len()
+                )));
+            }
+
+            // Responses may come in any order, so match them up by id.
+            let mut ordered: Vec<Option<Value>> = vec![None; len];
+            for response in responses {
+                let id = response
+                    .get("id")
+                    .and_then(Value::as_u64)
+                    .and_then(|id| ordered.get_mut(id as usize));
+                let Some(slot) = id else {
+                    return Err(AttemptError::Endpoint(anyhow!(
+                        "{method} batch returned a response with an unknown id"
+                    )));
+                };
+                *slot = Some(response);
+            }
+            let ordered: Vec<Value> =
+                ordered.into_iter().collect::<Option<_>>().ok_or_else(|| {
+                    AttemptError::Endpoint(anyhow!("{method} batch returned duplicate ids"))
+                })?;
+
+            let responses = ordered
+                .iter()
+                .map(|response| {
+                    serde_path_to_error::deserialize(response).map_err(|e| {
+                        match serde_json::from_value::<JsonRpcErrorResponse>(response.clone()) {
+                            Ok(response) => response.into_attempt_error(method),
+                            Err(_) => AttemptError::Response(decode_error(method, e)),
+                        }
+                    })
+                })
+                .collect::<Result<Vec<T>, _>>()?;
+            if let Some((cache, scope)) = cache {
+                // Stored in the order of `params`, so that the entry reads back
+                // as-is.
+                let bytes = serde_json::to_vec(&ordered).expect("JSON values always serialize");
+                self.write_cache(cache, scope, method, params, &bytes);
+            }
+            Ok(responses)
+        })
+        .await
+    }
+
+    /// Runs `attempt` against the active endpoint, failing over to the next
+    /// one whenever it fails with [`AttemptError::Endpoint`].
+    ///
+    /// Quarantined endpoints are skipped, and the request fails right away if
+    /// all of them are. Endpoints whose quarantine has ended are probed before
+    /// use.
+    async fn with_failover<T, F, Fut>(&self, method: &str, attempt: F) -> Result<T>
+    where
+        F: Fn(Url) -> Fut,
+        Fut: Future<Output = Result<T, AttemptError>>,
+    {
+        let active = self.active.load(Ordering::Relaxed);
+        let now = Instant::now();
+        let available: Vec<_> = (0..self.endpoints.len())
+            .map(|i| (active + i) % self.endpoints.len())
+            .filter(|&index| self.health[index].availability(now) != Availability::Quarantined)
+            .collect();
+        if available.is_empty() {
+            let retry_in = self
+                .health
+                .iter()
+                .filter_map(|health| health.remaining_cooldown(now))
+                .min()
+                .unwrap_or_default();
+            return Err(RpcError::Quarantined {
+                method: method.to_owned(),
+                retry_in,
+            }
+            .into());
+        }
+
+        let mut errors = Vec::new();
+        for index in available {
+            let endpoint = endpoint_name(&self.endpoints[index]);
+            let health = &self.health[index];
+            if health.availability(Instant::now()) == Availability::NeedsProbe {
+                if let Err(e) = self.probe(&self.endpoints[index]).await {
+                    warn!("RPC endpoint {endpoint} failed its probe: {e:#}");
+                    health.record_failure(Instant::now());
+                    errors.push((format!("{endpoint}: probe failed"), e));
+                    continue;
+                }
+                info!("RPC endpoint {endpoint} passed its probe, ending its quarantine");
+                health.record_success();
+            }
+
+            let started = Instant::now();
+            let result = attempt(self.endpoints[index].clone()).await;
+            if let Some(metrics) = &self.metrics {
+                metrics.record(method, started.elapsed(), result.is_ok());
+            }
+            let error = match result {
+                Ok(response) => {
+                    health.record_success();
+                    if index != active {
+                        warn!("failing over to RPC endpoint {endpoint}");
+                        self.active.store(index, Ordering::Relaxed);
+                    }
+                    return Ok(response);
+                }
+                Err(AttemptError::Endpoint(e)) => e,
+                Err(AttemptError::Response(e)) => {
+                    // Malformed responses count against the endpoint's health,
+                    // but may as well be our fault, so they do not fail over.
+                    self.record_failure(index);
+                    return Err(e);
+                }
+                Err(AttemptError::Rejected(e)) => {
+                    // The endpoint answered as it should.
+                    health.record_success();
+                    return Err(e);
+                }
+                Err(AttemptError::Local(e)) => return Err(e),
+            };
+            warn!("{method} failed on RPC endpoint {endpoint}: {error:#}");
+            self.record_failure(index);
+            errors.push((endpoint, error));
+        }
+
+        // Keep the last error as the cause, so that callers can classify it,
+        // and list the others in the context.
+        let (last_endpoint, last_error) = errors.pop().expect("there is at least one endpoint");
+        let mut context = format!("{method} failed on all RPC endpoints:");
+        for (endpoint, error) in errors {
+            context.push_str(&format!("\n{endpoint}: {error:#}"));
+        }
+        context.push_str(&format!("\n{last_endpoint}"));
+        Err(last_error.context(context))
+    }
+
+    fn record_failure(&self, index: usize) {
+        if self.health[index].record_failure(Instant::now()) {
+            warn!(
+                "quarantining RPC endpoint {}",
+                endpoint_name(&self.endpoints[index])
+            );
+        }
+    }
+
+    /// Checks that a recovering endpoint answers a cheap request.
+    async fn probe(&self, url: &Url) -> Result<(), anyhow::Error> {
+        let request = json!({
+            "jsonrpc": "2.0",
+            "method": "eth_chainId",
+            "params": [],
+            "id": 1,
+        });
+        let bytes = self
+            .post(url, "eth_chainId", &request, 1)
+            .await
+            .map_err(AttemptError::into_inner)?;
+        let response: Value = serde_json::from_slice(&bytes)?;
+        match response.get("result") {
+            Some(_) => Ok(()),
+            None => bail!("unexpected response {response}"),
+        }
+    }
+
+    /// Returns the call statistics, if enabled with `--rpc-metrics`.
+    pub fn metrics(&self) -> Option<&RpcMetrics> {
+        self.metrics.as_deref()
+    }
+
+    /// Returns the call statistics followed by the health of each endpoint,
+    /// formatted as tables, if enabled with `--rpc-metrics`.
+    pub fn metrics_summary(&self) -> Option<String> {
+        let metrics = self.metrics.as_deref()?;
+        let mut summary = format!(
+            "{metrics}\n{:<40} {:>8} {:>16}\n",
+            "endpoint", "failures", "quarantined (s)"
+        );
+        for status in self.endpoint_health() {
+            let quarantined = status
+                .quarantined_for_secs
+                .map_or_else(|| "-".to_owned(), |secs| secs.to_string());
+            summary.push_str(&format!(
+                "{:<40} {:>8} {:>16}\n",
+                status.endpoint, status.consecutive_failures, quarantined
+            ));
+        }
+        Some(summary)
+    }
+
+    /// Returns the health of each endpoint, in the order they were given.
+    pub fn endpoint_health(&self) -> Vec<EndpointStatus> {
+        let now = Instant::now();
+        self.endpoints
+            .iter()
+            .zip(self.health.iter())
+            .map(|(url, health)| health.snapshot(endpoint_name(url), now))
+            .collect()
+    }
+
+    /// Posts `request`, which counts as `requests` requests against the rate
+    /// limit, and returns the raw response body.
+    async fn post(
+        &self,
+        url: &Url,
+        method: &str,
+        request: &Value,
+        requests: usize,
+    ) -> Result<Bytes, AttemptError> {
+        if let Some(rate_limiter) = &self.rate_limiter {
+            for _ in 0..requests {
+                rate_limiter.acquire().await;
+            }
+        }
+
+        let transport_error = |e: reqwest::Error| {
+            let method = method.to_owned();
+            let source = e.without_url();
+            if source.is_timeout() {
+                RpcError::Timeout { method, source }
+            } else {
+                RpcError::Transport { method, source }
+            }
+        };
+        let too_large = || RpcError::ResponseTooLarge {
+            method: method.to_owned(),
+            limit: self.max_response_size,
+        };
+        let bytes = async {
+            let mut builder = self
+                .http
+                .post(url.clone())
+                .timeout(self.timeouts.for_method(method))
+                .json(request);
+            if let Some(auth) = &self.auth {
+                builder = auth.apply(builder);
+            }
+            let mut response = builder
+                .send()
+                .await
+                .and_then(reqwest::Response::error_for_status)
+                .map_err(transport_error)?;
+
+            // The body is read chunk by chunk, as the length is unknown for
+            // chunked or compressed responses.
+            if response
+                .content_length()
+                .is_some_and(|length| length > self.max_response_size)
+            {
+                return Err(too_large());
+            }
+            let mut body = Vec::new();
+            while let Some(chunk) = response.chunk().await.map_err(transport_error)? {
+                if (body.len() + chunk.len()) as u64 > self.max_response_size {
+                    return Err(too_large());
+                }
+                body.extend_from_slice(&chunk);
+            }
+            Ok(Bytes::from(body))
+        }
+        .await
+        .map_err(|e| AttemptError::Endpoint(e.into()))?;
+
+        if let Some(capture) = &self.capture {
+            capture
+                .record(method, request, &bytes)
+                .with_context(|| format!("capturing {method}"))
+                .map_err(AttemptError::Local)?;
+        }
+
+        Ok(bytes)
+    }
+}
+
+/// Identifies an endpoint in logs and errors without leaking credentials,
+/// which hosted providers often embed in the URL path or query.
+fn endpoint_name(url: &Url) -> String {
+    url.origin().ascii_serialization()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::mock_node::{self, MockNode, Reply};
+
+    #[derive(Deserialize, Debug)]
+    struct Response {
+        result: String,
+    }
+
+    fn result_reply(request: &Value) -> Reply {
+        Reply::Json(json!({ "jsonrpc": "2.0", "id": request["id"], "result": "0x1" }))
+    }
+
+    fn error_reply(request: &Value, code: i64, message: &str) -> Reply {
+        Reply::Json(json!({
+            "jsonrpc": "2.0",
+            "id": request["id"],
+            "error": { "code": code, "message": message },
+        }))
+    }
+
+    #[tokio::test]
+    async fn it_fails_over_when_the_endpoint_fails() {
+        let failing = MockNode::start(|_| Reply::Status(503)).await;
+        let lagging =
+            MockNode::start(|request| error_reply(request, -32000, "header not found")).await;
+        let healthy = MockNode::start(result_reply).await;
+        let client = mock_node::client(&[&failing, &lagging, &healthy], &[]);
+
+        for _ in 0..2 {
+            let response: Response = client.request("eth_chainId", json!([])).await.unwrap();
+            assert_eq!(response.result, "0x1");
+        }
+        // The healthy endpoint became the active one.
+        assert_eq!(failing.requests().len(), 1);
+        assert_eq!(lagging.requests().len(), 1);
+        assert_eq!(healthy.requests().len(), 2);
+        let health = client.endpoint_health();
+        assert_eq!(health[0].consecutive_failures, 1);
+        assert_eq!(health[1].consecutive_failures, 1);
+        assert_eq!(health[2].consecutive_failures, 0);
+    }
+
+    #[tokio::test]
+    async fn it_does_not_fail_over_when_the_request_is_rejected() {
+        let rejections = [
+            (-32601, "the method eth_foo does not exist"),
+            (-32602, "invalid argument 0"),
+            (3, "execution reverted"),
+            (-32000, "execution reverted: insufficient balance"),
+        ];
+        for (code, message) in rejections {
+            let rejecting =
+                MockNode::start(move |request| error_reply(request, code, message)).await;
+            let healthy = MockNode::start(result_reply).await;
+            let client = mock_node::client(
+                &[&rejecting, &healthy],
+                &["--rpc-quarantine-threshold", "1"],
+            );
+
+            for _ in 0..2 {
+                let error = client
+                    .request::<Response>("eth_call", json!([]))
+                    .await
+                    .unwrap_err();
+                assert_eq!(
+                    matches!(
+                        RpcError::find(&error),
+                        Some(RpcError::MethodNotFound { .. })
+                    ),
+                    code == METHOD_NOT_FOUND
+                );
+            }
+            assert_eq!(rejecting.requests().len(), 2);
+            assert!(healthy.requests().is_empty());
+            assert_eq!(client.endpoint_health()[0].consecutive_failures, 0);
+        }
+    }
+
+    #[tokio::test]
+    async fn it_does_not_blame_the_endpoint_for_capture_failures() {
+        let node = MockNode::start(result_reply).await;
+        let fallback = MockNode::start(result_reply).await;
+        let capture_dir = mock_node::temp_dir("capture");
+        let client = mock_node::client(
+            &[&node, &fallback],
+            &[
+                "--capture-rpc",
+                capture_dir.to_str().unwrap(),
+                "--rpc-quarantine-threshold",
+                "1",
+            ],
+        );
+        std::fs::remove_dir_all(&capture_dir).unwrap();
+
+        let error = client
+            .request::<Response>("eth_chainId", json!([]))
+            .await
+            .unwrap_err();
+        assert!(format!("{error:#}").contains("capturing eth_chainId"));
+        assert!(fallback.requests().is_empty());
+        assert_eq!(client.endpoint_health()[0].consecutive_failures, 0);
+    }
+
+    /// Starts a node that answers batches with `respond`, given the ids of
+    /// the requests.
+    async fn batch_node(respond: fn(Vec<Value>) -> Vec<Value>) -> MockNode {
+        MockNode::start(move |request| {
+            let ids = request
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|request| request["id"].clone())
+                .collect();
+            let responses = respond(ids)
+                .into_iter()
+                .map(|id| json!({ "jsonrpc": "2.0", "id": id, "result": format!("r{id}") }))
+                .collect();
+            Reply::Json(Value::Array(responses))
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn it_matches_batch_responses_by_id() {
+        let params = || vec![json!([0]), json!([1]), json!([2])];
+
+        let reversed = batch_node(|ids| ids.into_iter().rev().collect()).await;
+        let responses: Vec<Response> = mock_node::client(&[&reversed], &[])
+            .batch_request("eth_getBlockByNumber", params())
+            .await
+            .unwrap();
+        let results: Vec<_> = responses.into_iter().map(|r| r.result).collect();
+        assert_eq!(results, ["r0", "r1", "r2"]);
+
+        let duplicated =
+            batch_node(|ids| vec![ids[0].clone(), ids[0].clone(), ids[2].clone()]).await;
+        let error = mock_node::client(&[&duplicated], &[])
+            .batch_request::<Response>("eth_getBlockByNumber", params())
+            .await
+            .unwrap_err();
+        assert!(format!("{error:#}").contains("duplicate ids"));
+
+        let missing_id = batch_node(|ids| vec![ids[0].clone(), ids[1].clone(), Value::Null]).await;
+        let error = mock_node::client(&[&missing_id], &[])
+            .batch_request::<Response>("eth_getBlockByNumber", params())
+            .await
+            .unwrap_err();
+        assert!(format!("{error:#}").contains("unknown id"));
+
+        let missing_response = batch_node(|ids| ids[..2].to_vec()).await;
+        let error = mock_node::client(&[&missing_response], &[])
+            .batch_request::<Response>("eth_getBlockByNumber", params())
+            .await
+            .unwrap_err();
+        assert!(format!("{error:#}").contains("returned 2 responses to 3 requests"));
+    }
+
+    #[test]
+    fn it_requires_an_endpoint() {
+        use clap::Parser;
+
+        #[derive(Parser)]
+        struct Cli {
+            #[command(flatten)]
+            rpc: RpcArgs,
+        }
+
+        let mut cli = Cli::parse_from(["rpc", "--rpc-url", "http://node:8545"]);
+        assert!(cli.rpc.profile().is_ok());
+        // Built without clap, the arguments need not name an endpoint.
+        cli.rpc.rpc_url.clear();
+        assert_eq!(
+            cli.rpc.profile().unwrap_err().to_string(),
+            "either --rpc-url or --rpc-profile with --rpc-profiles is required"
+        );
+    }
+
+    #[test]
+    fn it_prefers_the_chain_flag_to_the_profile() {
+        use clap::Parser;
+
+        #[derive(Parser)]
+        struct Cli {
+            #[command(flatten)]
+            rpc: RpcArgs,
+        }
+
+        let cli = Cli::parse_from(["rpc", "--rpc-url", "http://node:8545"]);
+        let mut profile = cli.rpc.profile().unwrap();
+        assert_eq!(cli.rpc.chain_spec(None, &profile).unwrap().chain_id, None);
+        profile.chain_spec = ChainSpec::named("holesky");
+        assert_eq!(
+            cli.rpc.chain_spec(None, &profile).unwrap().chain_id,
+            Some(17_000)
+        );
+        assert_eq!(
+            cli.rpc
+                .chain_spec(Some("sepolia"), &profile)
+                .unwrap()
+                .chain_id,
+            Some(11_155_111)
+        );
+        assert!(cli.rpc.chain_spec(Some("no-such-chain"), &profile).is_err());
+    }
+}
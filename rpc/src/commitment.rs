@@ -0,0 +1,58 @@
+//! A hash-chain commitment over a range of blocks.
+//!
+//! Starting from zero, the hash and state root of each block are folded in
+//! order:
+//!
+//! ```text
+//! c_0 = 0x00..00
+//! c_i = keccak256(c_{i-1} || block_hash_i || state_root_i)
+//! ```
+//!
+//! Consumers of a proven range can recompute it from the block headers with
+//! `rpc commit-range` as a quick consistency check before verifying the
+//! proofs.
+use ethereum_types::H256;
+
+use crate::trie::keccak;
+
+/// Folds a block into the commitment over the blocks before it.
+pub fn fold(commitment: H256, block_hash: H256, state_root: H256) -> H256 {
+    let mut bytes = [0; 96];
+    bytes[..32].copy_from_slice(commitment.as_bytes());
+    bytes[32..64].copy_from_slice(block_hash.as_bytes());
+    bytes[64..].copy_from_slice(state_root.as_bytes());
+    keccak(&bytes)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn it_folds_blocks_in_order() {
+        let blocks = [
+            (H256::repeat_byte(1), H256::repeat_byte(2)),
+            (H256::repeat_byte(3), H256::repeat_byte(4)),
+        ];
+        let commitment = blocks
+            .iter()
+            .fold(H256::zero(), |c, &(hash, root)| fold(c, hash, root));
+
+        assert_eq!(
+            commitment,
+            keccak(
+                &[
+                    fold(H256::zero(), blocks[0].0, blocks[0].1).as_bytes(),
+                    &[3; 32],
+                    &[4; 32]
+                ]
+                .concat()
+            )
+        );
+        let reversed = blocks
+            .iter()
+            .rev()
+            .fold(H256::zero(), |c, &(hash, root)| fold(c, hash, root));
+        assert_ne!(commitment, reversed);
+    }
+}
@@ -0,0 +1,129 @@
+//! Semantic comparison of prover inputs.
+use std::{fs::File, io::BufReader, path::Path};
+
+use anyhow::{Context, Result};
+use prover::ProverInput;
+use serde_json::Value;
+
+/// Reads a reference prover input from a JSON file.
+pub fn load_prover_input<P: AsRef<Path>>(path: P) -> Result<ProverInput> {
+    let path = path.as_ref();
+    let file = BufReader::new(
+        File::open(path).with_context(|| format!("opening prover input {}", path.display()))?,
+    );
+    let des = &mut serde_json::Deserializer::from_reader(file);
+    serde_path_to_error::deserialize(des)
+        .with_context(|| format!("deserializing prover input {}", path.display()))
+}
+
+/// Compares two prover inputs, returning a description of every difference
+/// along with its JSON path.
+///
+/// Both inputs are compared in their canonical serialized form, so
+/// differences in encoding alone (e.g., hex case or leading zeros) are not
+/// reported.
+pub fn diff_prover_inputs(actual: &ProverInput, expected: &ProverInput) -> Result<Vec<String>> {
+    let mut differences = Vec::new();
+    diff_values(
+        "$",
+        &serde_json::to_value(actual)?,
+        &serde_json::to_value(expected)?,
+        &mut differences,
+    );
+    Ok(differences)
+}
+
+fn diff_values(path: &str, actual: &Value, expected: &Value, differences: &mut Vec<String>) {
+    match (actual, expected) {
+        (Value::Object(actual), Value::Object(expected)) => {
+            for (key, expected_value) in expected {
+                let path = format!("{path}.{key}");
+                match actual.get(key) {
+                    Some(actual_value) => {
+                        diff_values(&path, actual_value, expected_value, differences)
+                    }
+                    None => differences.push(format!("{path}: missing")),
+                }
+            }
+            for key in actual.keys().filter(|key| !expected.contains_key(*key)) {
+                differences.push(format!("{path}.{key}: unexpected"));
+            }
+        }
+        (Value::Array(actual), Value::Array(expected)) => {
+            if actual.len() != expected.len() {
+                differences.push(format!(
+                    "{path}: length {}, expected {}",
+                    actual.len(),
+                    expected.len()
+                ));
+            }
+            for (i, (actual, expected)) in actual.iter().zip(expected).enumerate() {
+                diff_values(&format!("{path}[{i}]"), actual, expected, differences);
+            }
+        }
+        _ if actual != expected => differences.push(format!(
+            "{path}: {}, expected {}",
+            abbreviate(actual),
+            abbreviate(expected)
+        )),
+        _ => {}
+    }
+}
+
+/// Renders a value for display, eliding the middle of long values such as
+/// encoded witnesses.
+fn abbreviate(value: &Value) -> String {
+    const MAX_LEN: usize = 80;
+
+    let rendered: Vec<char> = value.to_string().chars().collect();
+    if rendered.len() <= MAX_LEN {
+        return rendered.into_iter().collect();
+    }
+    let head: String = rendered[..MAX_LEN / 2].iter().collect();
+    let tail: String = rendered[rendered.len() - MAX_LEN / 2..].iter().collect();
+    format!("{head}...{tail} ({} chars)", rendered.len())
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::*;
+
+    fn diff(actual: Value, expected: Value) -> Vec<String> {
+        let mut differences = Vec::new();
+        diff_values("$", &actual, &expected, &mut differences);
+        differences
+    }
+
+    #[test]
+    fn it_reports_nothing_for_equal_values() {
+        let value = json!({"a": [1, {"b": "0x1"}], "c": null});
+        assert_eq!(diff(value.clone(), value), Vec::<String>::new());
+    }
+
+    #[test]
+    fn it_reports_differences_with_their_path() {
+        let actual = json!({"a": {"b": 1, "extra": true}, "c": [1, 2, 3]});
+        let expected = json!({"a": {"b": 2, "gone": false}, "c": [1, 5]});
+        assert_eq!(
+            diff(actual, expected),
+            [
+                "$.a.b: 1, expected 2",
+                "$.a.gone: missing",
+                "$.a.extra: unexpected",
+                "$.c: length 3, expected 2",
+                "$.c[1]: 2, expected 5",
+            ]
+        );
+    }
+
+    #[test]
+    fn it_abbreviates_long_values() {
+        let long = Value::String("ab".repeat(100));
+        let rendered = abbreviate(&long);
+        assert!(rendered.starts_with("\"abab"));
+        assert!(rendered.ends_with("abab\" (202 chars)"));
+        assert_eq!(abbreviate(&json!(7)), "7");
+    }
+}
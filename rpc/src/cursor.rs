@@ -0,0 +1,137 @@
+//! Cursor files, which make interval fetches resumable.
+use std::{fs, io::ErrorKind, ops::Range, path::PathBuf};
+
+use anyhow::{ensure, Context, Result};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+/// The progress of an interval fetch, as recorded in a cursor file.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+struct Cursor {
+    /// The start and end, excluded, of the interval.
+    range: [u64; 2],
+    /// The last block of the interval written.
+    last: u64,
+}
+
+/// A cursor file tracking the fetch of an interval.
+#[derive(Debug)]
+pub(crate) struct CursorFile {
+    path: PathBuf,
+    range: [u64; 2],
+}
+
+impl CursorFile {
+    pub(crate) fn new(path: PathBuf, blocks: &Range<u64>) -> Self {
+        Self {
+            path,
+            range: [blocks.start, blocks.end],
+        }
+    }
+
+    /// Returns the blocks of the interval after the last one written, or the
+    /// whole interval if the file does not exist yet.
+    ///
+    /// Fails if the file records another interval, so that a cursor is not
+    /// applied to a fetch it does not belong to.
+    pub(crate) fn remaining(&self) -> Result<Range<u64>> {
+        let [start, end] = self.range;
+        let Some(cursor) = self.read()? else {
+            return Ok(start..end);
+        };
+        let path = self.path.display();
+        ensure!(
+            cursor.range == self.range,
+            "cursor file {path} records the interval {}..{}, not {start}..{end}; delete it to \
+             fetch this interval from its start",
+            cursor.range[0],
+            cursor.range[1]
+        );
+        ensure!(
+            (start..end).contains(&cursor.last),
+            "cursor file {path} records block {}, outside of its interval",
+            cursor.last
+        );
+        info!(
+            "Resuming after block {}, as recorded in {path}",
+            cursor.last
+        );
+        Ok(cursor.last + 1..end)
+    }
+
+    fn read(&self) -> Result<Option<Cursor>> {
+        let bytes = match fs::read(&self.path) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => {
+                return Err(e)
+                    .with_context(|| format!("reading cursor file {}", self.path.display()))
+            }
+        };
+        serde_json::from_slice(&bytes)
+            .map(Some)
+            .with_context(|| format!("parsing cursor file {}", self.path.display()))
+    }
+
+    /// Atomically records `last` as the last block written.
+    pub(crate) fn write(&self, last: u64) -> Result<()> {
+        let cursor = Cursor {
+            range: self.range,
+            last,
+        };
+        let tmp = self.path.with_extension("tmp");
+        fs::write(&tmp, serde_json::to_vec(&cursor)?)?;
+        fs::rename(tmp, &self.path)
+            .with_context(|| format!("writing cursor file {}", self.path.display()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn cursor_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("rpc-cursor-{name}-{}", std::process::id()));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn it_resumes_after_the_last_block_written() {
+        let path = cursor_path("resume");
+        let cursor = CursorFile::new(path.clone(), &(100..200));
+        assert_eq!(cursor.remaining().unwrap(), 100..200);
+
+        cursor.write(123).unwrap();
+        cursor.write(149).unwrap();
+        assert_eq!(cursor.remaining().unwrap(), 150..200);
+        cursor.write(199).unwrap();
+        assert_eq!(cursor.remaining().unwrap(), 200..200);
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn it_rejects_the_cursor_of_another_interval() {
+        let path = cursor_path("other");
+        CursorFile::new(path.clone(), &(400..600))
+            .write(500)
+            .unwrap();
+
+        let error = CursorFile::new(path.clone(), &(100..200))
+            .remaining()
+            .unwrap_err();
+        assert!(error.to_string().contains("records the interval 400..600"));
+
+        fs::write(&path, r#"{"range":[100,200],"last":50}"#).unwrap();
+        assert!(CursorFile::new(path.clone(), &(100..200))
+            .remaining()
+            .is_err());
+        fs::write(&path, "149").unwrap();
+        assert!(CursorFile::new(path.clone(), &(100..200))
+            .remaining()
+            .is_err());
+
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,142 @@
+//! DNS resolution of RPC endpoints.
+//!
+//! Resolver hiccups in some cloud networks fail single requests in the middle
+//! of a fetch. [`CachingResolver`] keeps the addresses of every host it
+//! resolves, reuses them for a while, and falls back to them when a later
+//! lookup fails.
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use hyper::client::connect::dns::Name;
+use reqwest::dns::{Addrs, Resolve, Resolving};
+use tracing::warn;
+
+use crate::client::IpVersion;
+
+#[derive(Debug)]
+struct CachedAddrs {
+    resolved_at: Instant,
+    addrs: Vec<SocketAddr>,
+}
+
+/// A resolver using the system's, which caches answers for `ttl` and serves
+/// stale answers when a lookup fails.
+#[derive(Debug, Clone)]
+pub(crate) struct CachingResolver {
+    ttl: Duration,
+    ip_version: IpVersion,
+    cache: Arc<Mutex<HashMap<String, CachedAddrs>>>,
+}
+
+impl CachingResolver {
+    pub(crate) fn new(ttl: Duration, ip_version: IpVersion) -> Self {
+        Self {
+            ttl,
+            ip_version,
+            cache: Default::default(),
+        }
+    }
+
+    fn cached(&self, host: &str, fresh: bool) -> Option<Vec<SocketAddr>> {
+        let cache = self.cache.lock().unwrap();
+        cache
+            .get(host)
+            .filter(|cached| !fresh || cached.resolved_at.elapsed() < self.ttl)
+            .map(|cached| cached.addrs.clone())
+    }
+
+    async fn lookup(&self, host: &str) -> std::io::Result<Vec<SocketAddr>> {
+        if let Some(addrs) = self.cached(host, true) {
+            return Ok(addrs);
+        }
+
+        // The port is replaced by the endpoint's when connecting.
+        let lookup = tokio::net::lookup_host((host, 0)).await.and_then(|addrs| {
+            let addrs: Vec<_> = addrs.filter(|addr| self.ip_version.accepts(addr)).collect();
+            if addrs.is_empty() {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::NotFound,
+                    format!("{host} has no {} address", self.ip_version.name()),
+                ));
+            }
+            Ok(addrs)
+        });
+        match lookup {
+            Ok(addrs) => {
+                self.cache.lock().unwrap().insert(
+                    host.to_owned(),
+                    CachedAddrs {
+                        resolved_at: Instant::now(),
+                        addrs: addrs.clone(),
+                    },
+                );
+                Ok(addrs)
+            }
+            Err(e) => match self.cached(host, false) {
+                Some(addrs) => {
+                    warn!("Resolving {host} failed, reusing its previous addresses: {e}");
+                    Ok(addrs)
+                }
+                None => Err(e),
+            },
+        }
+    }
+}
+
+impl Resolve for CachingResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let resolver = self.clone();
+        Box::pin(async move {
+            let addrs = resolver.lookup(name.as_str()).await?;
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const TTL: Duration = Duration::from_secs(60);
+
+    fn resolver_with(host: &str, age: Duration, addrs: Vec<SocketAddr>) -> CachingResolver {
+        let resolver = CachingResolver::new(TTL, IpVersion::Any);
+        resolver.cache.lock().unwrap().insert(
+            host.to_owned(),
+            CachedAddrs {
+                resolved_at: Instant::now() - age,
+                addrs,
+            },
+        );
+        resolver
+    }
+
+    #[tokio::test]
+    async fn it_reuses_fresh_answers() {
+        let addrs = vec!["10.0.0.1:0".parse().unwrap()];
+        let resolver = resolver_with("node.invalid", Duration::ZERO, addrs.clone());
+        assert_eq!(resolver.lookup("node.invalid").await.unwrap(), addrs);
+    }
+
+    #[tokio::test]
+    async fn it_resolves_again_after_the_ttl() {
+        let stale = vec!["10.0.0.1:0".parse().unwrap()];
+        let resolver = resolver_with("localhost", TTL, stale.clone());
+        let addrs = resolver.lookup("localhost").await.unwrap();
+        assert!(addrs.iter().all(|addr| addr.ip().is_loopback()));
+        assert_eq!(resolver.cached("localhost", true), Some(addrs));
+    }
+
+    #[tokio::test]
+    async fn it_falls_back_to_stale_answers() {
+        let stale = vec!["10.0.0.1:0".parse().unwrap()];
+        // `.invalid` names never resolve.
+        let resolver = resolver_with("node.invalid", TTL * 2, stale.clone());
+        assert_eq!(resolver.lookup("node.invalid").await.unwrap(), stale);
+        assert!(resolver.lookup("other.invalid").await.is_err());
+    }
+}
@@ -0,0 +1,112 @@
+//! Errors of the fetcher that callers may want to act on.
+//!
+//! Functions of this crate return [`anyhow::Error`]s, whose root cause is an
+//! [`RpcError`] when the failure falls into one of its categories. Use
+//! [`RpcError::find`] to look it up.
+use std::time::Duration;
+
+use ethereum_types::{H256, U256};
+use thiserror::Error;
+
+use crate::block_id::BlockId;
+
+#[derive(Error, Debug)]
+pub enum RpcError {
+    /// The node does not have the block, even after retrying.
+    #[error("block {0} not found")]
+    BlockNotFound(BlockId),
+    /// The block is too far past the node's head to be about to appear.
+    #[error("block {number} does not exist; the node's head is block {head}")]
+    BeyondHead { number: u64, head: u64 },
+    /// The node returned blocks from different forks.
+    #[error(
+        "block {child} does not build on block {ancestor} ({ancestor_hash:?}); the chain was \
+         likely reorganized during the fetch"
+    )]
+    Reorged {
+        child: U256,
+        ancestor: U256,
+        ancestor_hash: H256,
+    },
+    /// The request did not complete within its timeout.
+    #[error("fetching {method} timed out")]
+    Timeout {
+        method: String,
+        #[source]
+        source: reqwest::Error,
+    },
+    /// Every endpoint is quarantined after failing repeatedly, so the request
+    /// was not sent.
+    #[error(
+        "{method} was not sent, as all RPC endpoints are quarantined for another {}s",
+        retry_in.as_secs_f64().ceil()
+    )]
+    Quarantined { method: String, retry_in: Duration },
+    /// The endpoint could not be reached or answered with an HTTP error.
+    #[error("fetching {method}")]
+    Transport {
+        method: String,
+        #[source]
+        source: reqwest::Error,
+    },
+    /// The response exceeds the configured maximum size.
+    #[error("{method} returned more than {limit} bytes")]
+    ResponseTooLarge { method: String, limit: u64 },
+    /// The node does not serve the method, typically because its namespace
+    /// is disabled.
+    #[error(
+        "{method} is not available on the node{}",
+        method_not_found_hint(method)
+    )]
+    MethodNotFound { method: String },
+    /// The node answered with a JSON-RPC error.
+    #[error("{method} returned error {code}: {message}")]
+    Node {
+        method: String,
+        code: i64,
+        message: String,
+    },
+    /// The response is not what the method should return.
+    #[error("deserializing {method}")]
+    Decode {
+        method: String,
+        #[source]
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+    #[error("fetching block {0} was cancelled")]
+    Cancelled(BlockId),
+}
+
+impl RpcError {
+    /// Returns the [`RpcError`] in the chain of `error`, if any.
+    pub fn find(error: &anyhow::Error) -> Option<&RpcError> {
+        error.chain().find_map(|cause| cause.downcast_ref())
+    }
+
+    /// Whether fetching again may succeed without changing the request or the
+    /// node.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            RpcError::BlockNotFound(_)
+            | RpcError::Reorged { .. }
+            | RpcError::Quarantined { .. }
+            | RpcError::Timeout { .. }
+            | RpcError::Transport { .. } => true,
+            RpcError::BeyondHead { .. }
+            | RpcError::ResponseTooLarge { .. }
+            | RpcError::MethodNotFound { .. }
+            | RpcError::Node { .. }
+            | RpcError::Decode { .. }
+            | RpcError::Cancelled(_) => false,
+        }
+    }
+}
+
+fn method_not_found_hint(method: &str) -> &'static str {
+    if method.starts_with("debug_") {
+        "; block traces require the debug namespace of a Jerigon node, enabled with \
+         `--http.api=eth,debug`"
+    } else {
+        ""
+    }
+}
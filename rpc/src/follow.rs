@@ -0,0 +1,338 @@
+//! Follow mode: fetches the prover input of every new block.
+use std::{fs, io::Write, path::PathBuf, time::Duration};
+
+use anyhow::{Context, Result};
+use ethereum_types::H256;
+use prover::ProverInput;
+use rpc::{
+    block_id::BlockId, client::RpcClient, error::RpcError, fetch_head, fetch_prover_input,
+    FetchProverInputRequest,
+};
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+use crate::manifest::Manifest;
+
+/// Fetches the prover input of every block once it is `confirmations` blocks
+/// deep, polling the node's head every `poll_interval`.
+///
+/// Starts at `start`, or at the most recent block that is deep enough. Each
+/// prover input is written to `output`. A fetch that fails with a retryable
+/// error, including that of the start block or of the head at startup, is
+/// retried at the next poll, and a block that does not build on the
+/// previously written one fails with [`RpcError::Reorged`].
+///
+/// Returns once `cancel` is cancelled, abandoning the block being fetched.
+/// Every block written until then is complete.
+pub(crate) async fn follow<'a>(
+    client: &RpcClient,
+    start: Option<BlockId>,
+    confirmations: u64,
+    poll_interval: Duration,
+    mut output: Output,
+    cancel: &CancellationToken,
+    request: impl Fn(BlockId) -> FetchProverInputRequest<'a>,
+) -> Result<()> {
+    // The next block to fetch, known once the start block is written or the
+    // head is first fetched.
+    let mut next = None;
+
+    'follow: loop {
+        let head = tokio::select! {
+            () = cancel.cancelled() => break,
+            head = fetch_head(client) => head,
+        };
+        match head {
+            Ok(head) => {
+                if next.is_none() {
+                    next = match start {
+                        Some(block_id) => match fetch_prover_input(request(block_id)).await {
+                            Ok(prover_input) => {
+                                output.write(&prover_input)?;
+                                Some(prover_input.get_block_number().as_u64() + 1)
+                            }
+                            Err(_) if cancel.is_cancelled() => break 'follow,
+                            Err(e) if is_retryable(&e) => {
+                                warn!(
+                                    "Fetching block {block_id} failed, retrying at the next \
+                                     poll: {e:#}"
+                                );
+                                None
+                            }
+                            Err(e) => return Err(output.failed(block_id, e)),
+                        },
+                        None => Some(head.saturating_sub(confirmations)),
+                    };
+                    if let Some(next) = next {
+                        info!(
+                            "Following the chain from block {next}, {confirmations} block(s) \
+                             behind its head"
+                        );
+                    }
+                }
+                while let Some(number) = next.filter(|&n| n + confirmations <= head) {
+                    let prover_input = match fetch_prover_input(request(number.into())).await {
+                        Ok(prover_input) => prover_input,
+                        Err(_) if cancel.is_cancelled() => break 'follow,
+                        Err(e) if is_retryable(&e) => {
+                            warn!(
+                                "Fetching block {number} failed, retrying at the next poll: {e:#}"
+                            );
+                            break;
+                        }
+                        Err(e) => return Err(output.failed(number.into(), e)),
+                    };
+                    output.write(&prover_input)?;
+                    next = Some(number + 1);
+                }
+            }
+            Err(e) if is_retryable(&e) => {
+                warn!("Fetching the head failed, retrying at the next poll: {e:#}")
+            }
+            Err(e) => return Err(e),
+        }
+        tokio::select! {
+            () = cancel.cancelled() => break,
+            () = tokio::time::sleep(poll_interval) => {}
+        }
+    }
+    match next {
+        Some(next) => info!("Stopped following the chain before block {next}"),
+        None => info!("Stopped before following the chain"),
+    }
+    Ok(())
+}
+
+fn is_retryable(error: &anyhow::Error) -> bool {
+    RpcError::find(error).is_some_and(RpcError::is_retryable)
+}
+
+/// Where the prover inputs go, and the hash of the last one written.
+pub(crate) struct Output {
+    dir: Option<PathBuf>,
+    manifest: Option<Manifest>,
+    last_hash: Option<H256>,
+}
+
+impl Output {
+    /// Writes prover inputs to `<dir>/block-<number>.json`, or, without a
+    /// directory, to stdout on a line of their own, optionally listing them in
+    /// a manifest.
+    pub(crate) fn new(dir: Option<PathBuf>, manifest: Option<Manifest>) -> Result<Self> {
+        if let Some(dir) = &dir {
+            fs::create_dir_all(dir)?;
+        }
+        Ok(Self {
+            dir,
+            manifest,
+            last_hash: None,
+        })
+    }
+
+    /// Writes a prover input, after checking that it builds on the previous
+    /// one.
+    fn write(&mut self, prover_input: &ProverInput) -> Result<()> {
+        let number = prover_input.get_block_number();
+        let hashes = &prover_input.other_data.b_data.b_hashes;
+        if let Some(last_hash) = self.last_hash {
+            if hashes.prev_hashes.last() != Some(&last_hash) {
+                let error = anyhow::Error::from(RpcError::Reorged {
+                    child: number,
+                    ancestor: number - 1,
+                    ancestor_hash: last_hash,
+                })
+                .context(format!(
+                    "block {} was written but is no longer canonical; consider more confirmations",
+                    number - 1
+                ));
+                return Err(self.failed(BlockId::Number(number.as_u64()), error));
+            }
+        }
+
+        let bytes = serde_json::to_vec(prover_input)?;
+        match &self.dir {
+            Some(dir) => {
+                let path = dir.join(format!("block-{number}.json"));
+                fs::write(&path, &bytes).with_context(|| format!("writing {}", path.display()))?;
+                if let Some(manifest) = &mut self.manifest {
+                    manifest.record_written(number.as_u64(), hashes.cur_hash, &path, &bytes)?;
+                }
+            }
+            None => {
+                let mut stdout = std::io::stdout().lock();
+                stdout.write_all(&bytes)?;
+                stdout.write_all(b"\n")?;
+                stdout.flush()?;
+            }
+        }
+        info!("Wrote the prover input of block {number}");
+        self.last_hash = Some(hashes.cur_hash);
+        Ok(())
+    }
+
+    /// Records in the manifest that the given block failed with `error`,
+    /// which is returned. Only blocks given by number are recorded.
+    fn failed(&mut self, block_id: BlockId, error: anyhow::Error) -> anyhow::Error {
+        if let (Some(manifest), BlockId::Number(number)) = (&mut self.manifest, block_id) {
+            manifest.record_failed(number, &error);
+        }
+        error
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use clap::Parser;
+    use evm_arithmetization::proof::{BlockHashes, BlockMetadata};
+    use rpc::{chain_spec::ChainSpec, client::RpcArgs, header_defaults::HeaderDefaults};
+    use trace_decoder::{
+        trace_protocol::{BlockTrace, BlockTraceTriePreImages, CombinedPreImages, TrieCompact},
+        types::{BlockLevelData, OtherBlockData},
+    };
+
+    use super::*;
+
+    /// A prover input for block `number`, whose parent has hash `parent`.
+    fn prover_input(number: u64, parent: H256) -> ProverInput {
+        let mut prev_hashes = vec![H256::zero(); 256];
+        prev_hashes[255] = parent;
+        ProverInput {
+            block_trace: BlockTrace {
+                trie_pre_images: BlockTraceTriePreImages::Combined(CombinedPreImages {
+                    compact: TrieCompact(Vec::new()),
+                }),
+                txn_info: Vec::new(),
+            },
+            other_data: OtherBlockData {
+                b_data: BlockLevelData {
+                    b_meta: BlockMetadata {
+                        block_number: number.into(),
+                        ..Default::default()
+                    },
+                    b_hashes: BlockHashes {
+                        prev_hashes,
+                        cur_hash: H256::from_low_u64_be(number),
+                    },
+                    withdrawals: Vec::new(),
+                },
+                checkpoint_state_trie_root: H256::zero(),
+            },
+            overridden: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn it_writes_blocks_that_build_on_the_previous_one() {
+        let dir = std::env::temp_dir().join(format!("rpc-follow-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let mut output = Output::new(Some(dir.clone()), None).unwrap();
+
+        // The first block is written whatever its parent.
+        output
+            .write(&prover_input(10, H256::repeat_byte(9)))
+            .unwrap();
+        output
+            .write(&prover_input(11, H256::from_low_u64_be(10)))
+            .unwrap();
+        assert_eq!(output.last_hash, Some(H256::from_low_u64_be(11)));
+        assert!(dir.join("block-10.json").exists());
+        assert!(dir.join("block-11.json").exists());
+
+        let written: ProverInput =
+            serde_json::from_slice(&fs::read(dir.join("block-11.json")).unwrap()).unwrap();
+        assert_eq!(written.get_block_number(), 11.into());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn it_stops_at_a_reorg() {
+        let dir = std::env::temp_dir().join(format!("rpc-follow-reorg-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let manifest = Some(Manifest::open(dir.join("manifest.json")).unwrap());
+        let mut output = Output::new(Some(dir.clone()), manifest).unwrap();
+        output
+            .write(&prover_input(10, H256::from_low_u64_be(9)))
+            .unwrap();
+
+        // Block 11 of another fork, whose block 10 differs.
+        let error = output
+            .write(&prover_input(11, H256::repeat_byte(0xaa)))
+            .unwrap_err();
+        assert!(matches!(
+            RpcError::find(&error),
+            Some(RpcError::Reorged { ancestor_hash, .. })
+                if *ancestor_hash == H256::from_low_u64_be(10)
+        ));
+        assert!(!dir.join("block-11.json").exists());
+        assert_eq!(output.last_hash, Some(H256::from_low_u64_be(10)));
+
+        let manifest: serde_json::Value =
+            serde_json::from_slice(&fs::read(dir.join("manifest.json")).unwrap()).unwrap();
+        let statuses: Vec<_> = manifest["blocks"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|entry| (entry["block"].clone(), entry["status"].clone()))
+            .collect();
+        assert_eq!(
+            statuses,
+            [(10.into(), "written".into()), (11.into(), "failed".into())]
+        );
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn it_retries_until_the_node_is_reachable() {
+        #[derive(Parser)]
+        struct Cli {
+            #[command(flatten)]
+            rpc: RpcArgs,
+        }
+
+        // A port that nothing listens on.
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let url = format!("http://127.0.0.1:{port}");
+        let cli = Cli::parse_from(["rpc", "--rpc-url", &url, "--rpc-timeout", "1"]);
+        let client = cli.rpc.client(&cli.rpc.profile().unwrap()).unwrap();
+        let chain_spec = ChainSpec::default();
+        let header_defaults = HeaderDefaults::default();
+
+        for start in [None, Some(BlockId::Number(10))] {
+            let cancel = CancellationToken::new();
+            let request = |block_id| FetchProverInputRequest {
+                client: &client,
+                block_id,
+                checkpoint_block_id: BlockId::Number(0),
+                chain_spec: &chain_spec,
+                header_defaults: &header_defaults,
+                prev_hash_concurrency: 1.try_into().unwrap(),
+                cancel: &cancel,
+            };
+            let cancel_soon = async {
+                tokio::time::sleep(Duration::from_millis(300)).await;
+                cancel.cancel();
+            };
+            let output = Output::new(None, None).unwrap();
+            let (followed, ()) = tokio::join!(
+                follow(
+                    &client,
+                    start,
+                    0,
+                    Duration::from_millis(10),
+                    output,
+                    &cancel,
+                    request
+                ),
+                cancel_soon
+            );
+            followed.unwrap();
+        }
+    }
+}
@@ -0,0 +1,75 @@
+//! Fallback values for block header fields that a node does not return.
+//!
+//! Chains that are not (yet) fully type-1 compatible often omit header fields
+//! the prover relies on. Rather than failing the fetch, callers may opt into
+//! substituting defaults, either globally with `--tolerant` or per field.
+use clap::Args;
+use ethereum_types::{H256, U256};
+
+/// The help heading for the header default arguments.
+const HEADING: &str = "Header defaults";
+
+#[derive(Args, Debug, Clone, Default)]
+pub struct HeaderDefaults {
+    /// Substitute zero/empty values for any header field missing from the
+    /// node's response instead of failing.
+    #[arg(long, help_heading = HEADING)]
+    pub tolerant: bool,
+    /// The mix hash to use when the header has none.
+    #[arg(long, help_heading = HEADING)]
+    pub default_mix_hash: Option<H256>,
+    /// The base fee (in wei) to use when the header has none.
+    #[arg(long, help_heading = HEADING, value_parser = parse_u256)]
+    pub default_base_fee: Option<U256>,
+}
+
+impl HeaderDefaults {
+    /// The mix hash to substitute for a missing one, if allowed.
+    pub fn mix_hash(&self) -> Option<H256> {
+        self.default_mix_hash
+            .or_else(|| self.tolerant.then(H256::zero))
+    }
+
+    /// The base fee to substitute for a missing one, if allowed.
+    pub fn base_fee(&self) -> Option<U256> {
+        self.default_base_fee
+            .or_else(|| self.tolerant.then(U256::zero))
+    }
+
+    /// Whether missing withdrawals may be treated as an empty list.
+    pub fn withdrawals(&self) -> bool {
+        self.tolerant
+    }
+}
+
+/// Parses a decimal or `0x`-prefixed hexadecimal [`U256`].
+///
+/// [`U256`]'s [`FromStr`](std::str::FromStr) implementation always assumes
+/// hexadecimal, which is surprising for a command line value.
+fn parse_u256(s: &str) -> Result<U256, String> {
+    match s.strip_prefix("0x") {
+        Some(hex) => U256::from_str_radix(hex, 16).map_err(|e| e.to_string()),
+        None => U256::from_dec_str(s).map_err(|e| e.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn it_parses_decimal_u256() {
+        assert_eq!(parse_u256("100"), Ok(U256::from(100)));
+    }
+
+    #[test]
+    fn it_parses_hex_u256() {
+        assert_eq!(parse_u256("0x64"), Ok(U256::from(100)));
+    }
+
+    #[test]
+    fn it_rejects_invalid_u256() {
+        assert!(parse_u256("0xzz").is_err());
+        assert!(parse_u256("ten").is_err());
+    }
+}
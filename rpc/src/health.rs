@@ -0,0 +1,142 @@
+//! Health tracking of RPC endpoints.
+//!
+//! Each endpoint has a circuit breaker. An endpoint that fails several
+//! requests in a row is quarantined for a cooldown period, during which
+//! requests go to the other endpoints, or fail right away if there are none.
+//! Once the cooldown has passed, the endpoint must answer a probe before it is
+//! used again.
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use serde::Serialize;
+
+/// When to quarantine an endpoint, and for how long.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct QuarantinePolicy {
+    /// The number of consecutive failures after which an endpoint is
+    /// quarantined.
+    pub(crate) threshold: u32,
+    /// How long a quarantined endpoint is skipped before being probed.
+    pub(crate) cooldown: Duration,
+}
+
+/// Whether an endpoint may be used for a request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Availability {
+    Available,
+    /// The cooldown has passed, but the endpoint must answer a probe first.
+    NeedsProbe,
+    Quarantined,
+}
+
+#[derive(Debug)]
+pub(crate) struct EndpointHealth {
+    policy: QuarantinePolicy,
+    state: Mutex<State>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    consecutive_failures: u32,
+    quarantined_until: Option<Instant>,
+}
+
+impl EndpointHealth {
+    pub(crate) fn new(policy: QuarantinePolicy) -> Self {
+        Self {
+            policy,
+            state: Mutex::default(),
+        }
+    }
+
+    pub(crate) fn availability(&self, now: Instant) -> Availability {
+        match self.state.lock().unwrap().quarantined_until {
+            None => Availability::Available,
+            Some(until) if now >= until => Availability::NeedsProbe,
+            Some(_) => Availability::Quarantined,
+        }
+    }
+
+    pub(crate) fn record_success(&self) {
+        *self.state.lock().unwrap() = State::default();
+    }
+
+    /// Records a failure, returning whether it put the endpoint into
+    /// quarantine.
+    pub(crate) fn record_failure(&self, now: Instant) -> bool {
+        let mut state = self.state.lock().unwrap();
+        state.consecutive_failures += 1;
+        // A failed probe restarts the cooldown.
+        let quarantine = state.quarantined_until.is_some()
+            || state.consecutive_failures >= self.policy.threshold;
+        if quarantine {
+            state.quarantined_until = Some(now + self.policy.cooldown);
+        }
+        quarantine
+    }
+
+    /// Returns the time left until a quarantined endpoint may be probed.
+    pub(crate) fn remaining_cooldown(&self, now: Instant) -> Option<Duration> {
+        let state = self.state.lock().unwrap();
+        state
+            .quarantined_until
+            .map(|until| until.saturating_duration_since(now))
+    }
+
+    pub(crate) fn snapshot(&self, endpoint: String, now: Instant) -> EndpointStatus {
+        let state = self.state.lock().unwrap();
+        EndpointStatus {
+            endpoint,
+            consecutive_failures: state.consecutive_failures,
+            quarantined_for_secs: state
+                .quarantined_until
+                .map(|until| until.saturating_duration_since(now).as_secs()),
+        }
+    }
+}
+
+/// The health of an endpoint at a point in time.
+#[derive(Debug, Clone, Serialize)]
+pub struct EndpointStatus {
+    /// The endpoint's origin; the rest of the URL may hold credentials.
+    pub endpoint: String,
+    pub consecutive_failures: u32,
+    /// The remaining cooldown in seconds if the endpoint is quarantined.
+    /// Zero once the endpoint is awaiting a probe.
+    pub quarantined_for_secs: Option<u64>,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn it_quarantines_and_reinstates_endpoints() {
+        let policy = QuarantinePolicy {
+            threshold: 3,
+            cooldown: Duration::from_secs(30),
+        };
+        let health = EndpointHealth::new(policy);
+        let now = Instant::now();
+
+        for _ in 1..policy.threshold {
+            assert!(!health.record_failure(now));
+        }
+        assert!(health.record_failure(now));
+        assert_eq!(health.availability(now), Availability::Quarantined);
+
+        let later = now + policy.cooldown;
+        assert_eq!(health.availability(later), Availability::NeedsProbe);
+        // A failed probe quarantines the endpoint again right away.
+        assert!(health.record_failure(later));
+        assert_eq!(health.availability(later), Availability::Quarantined);
+
+        health.record_success();
+        assert_eq!(
+            health.availability(later + policy.cooldown),
+            Availability::Available
+        );
+    }
+}
@@ -0,0 +1,81 @@
+//! JWT authentication for the authenticated RPC port of execution clients, as
+//! specified for the engine API.
+//!
+//! Every request carries a fresh HS256 token whose only claim is the time it
+//! was issued at, since nodes reject tokens issued more than a minute away
+//! from their clock.
+use std::{
+    fs,
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{ensure, Context, Result};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+/// The base64url-encoded header of every token, `{"alg":"HS256","typ":"JWT"}`.
+const HEADER: &str = "eyJhbGciOiJIUzI1NiIsInR5cCI6IkpXVCJ9";
+
+/// The secret shared with the node, read from the same hex file the node
+/// uses (e.g., Erigon's and geth's `jwt.hex`).
+#[derive(Clone)]
+pub(crate) struct JwtSecret([u8; 32]);
+
+impl JwtSecret {
+    pub(crate) fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("reading JWT secret {}", path.display()))?;
+        let hex = contents.trim();
+        let bytes = hex::decode(hex.strip_prefix("0x").unwrap_or(hex))
+            .with_context(|| format!("decoding JWT secret {}", path.display()))?;
+        ensure!(
+            bytes.len() == 32,
+            "JWT secret {} is {} bytes long, expected 32",
+            path.display(),
+            bytes.len()
+        );
+        Ok(Self(bytes.try_into().unwrap()))
+    }
+
+    /// Returns a token issued now.
+    pub(crate) fn token(&self) -> String {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("system clock is before the UNIX epoch");
+        self.token_issued_at(now.as_secs())
+    }
+
+    fn token_issued_at(&self, iat: u64) -> String {
+        let claims = URL_SAFE_NO_PAD.encode(format!(r#"{{"iat":{iat}}}"#));
+        let message = format!("{HEADER}.{claims}");
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(&self.0).expect("HMAC accepts keys of any length");
+        mac.update(message.as_bytes());
+        let signature = URL_SAFE_NO_PAD.encode(mac.finalize().into_bytes());
+        format!("{message}.{signature}")
+    }
+}
+
+impl std::fmt::Debug for JwtSecret {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("JwtSecret(..)")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn it_signs_tokens() {
+        let secret = JwtSecret(std::array::from_fn(|i| i as u8));
+        assert_eq!(
+            secret.token_issued_at(1700000000),
+            "eyJhbGciOiJIUzI1NiIsInR5cCI6IkpXVCJ9.eyJpYXQiOjE3MDAwMDAwMDB9.\
+             uAxdaUtr1_HpYPtNDLNlQ_sHMiP5eBV-ra8EjL3ScS8"
+        );
+    }
+}
@@ -1,2 +1,26 @@
+pub mod block_id;
+mod cache;
+pub mod chain_spec;
+pub mod client;
+pub mod commitment;
+pub mod compare;
+mod dns;
+pub mod error;
+pub mod header_defaults;
+pub mod health;
+mod jwt;
+pub mod lint;
+pub mod metrics;
+#[cfg(test)]
+mod mock_node;
+pub mod overrides;
+pub mod profile;
+mod rate_limit;
+mod receipt;
 mod rpc;
-pub use rpc::{fetch_prover_input, FetchProverInputRequest};
+pub mod signal;
+pub mod trie;
+pub use rpc::{
+    fetch_commitment_of_blocks, fetch_head, fetch_pre_state, fetch_prover_input,
+    fetch_range_commitment, FetchProverInputRequest, PreState,
+};
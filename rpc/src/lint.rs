@@ -0,0 +1,411 @@
+//! Structural checks on prover inputs.
+//!
+//! Proving a malformed witness typically fails deep inside the decoder or the
+//! prover, one problem at a time. The checks of [`lint_prover_input`] are
+//! cheap, need no node or decoder, and report every violation found at once.
+//!
+//! The state witness is checked by [`lint_witness`], which runs the decoder:
+//! contract code, account proofs and storage live in the compact trie
+//! encoding, which only the decoder parses.
+use std::panic::{self, AssertUnwindSafe};
+
+use anyhow::Result;
+use ethereum_types::{Bloom, H256, U256};
+use prover::ProverInput;
+
+use crate::{chain_spec::PREV_HASHES_LEN, receipt::Receipt};
+
+/// Checks the structural invariants of a prover input, returning a
+/// description of every violation.
+pub fn lint_prover_input(input: &ProverInput) -> Vec<String> {
+    let mut violations = Vec::new();
+
+    let b_data = &input.other_data.b_data;
+    let block_number = b_data.b_meta.block_number;
+    let prev_hashes = &b_data.b_hashes.prev_hashes;
+
+    if prev_hashes.len() != PREV_HASHES_LEN {
+        violations.push(format!(
+            "expected {PREV_HASHES_LEN} previous block hashes, got {}",
+            prev_hashes.len()
+        ));
+    }
+    // Hashes are zeroed only as padding for blocks outside of the window, so
+    // any zero hash must precede all non-zero ones.
+    if let Some(first) = prev_hashes.iter().position(|h| !h.is_zero()) {
+        if let Some(gap) = prev_hashes[first..].iter().position(H256::is_zero) {
+            violations.push(format!(
+                "previous block hash {} is zero but follows non-zero hashes",
+                first + gap
+            ));
+        }
+    }
+    if !block_number.is_zero() && prev_hashes.last().is_none_or(H256::is_zero) {
+        violations.push(format!(
+            "block {block_number} is not genesis but its parent hash is zero"
+        ));
+    }
+    if b_data.b_hashes.cur_hash.is_zero() {
+        violations.push("block hash is zero".to_string());
+    }
+    if input.other_data.checkpoint_state_trie_root.is_zero() {
+        violations.push("checkpoint state trie root is zero".to_string());
+    }
+
+    for (i, txn) in input.block_trace.txn_info.iter().enumerate() {
+        if txn.meta.new_txn_trie_node_byte.is_empty() {
+            violations.push(format!("transaction {i} has no encoded transaction"));
+        }
+        if txn.meta.new_receipt_trie_node_byte.is_empty() {
+            violations.push(format!("transaction {i} has no encoded receipt"));
+        }
+    }
+
+    violations.extend(check_receipts(input));
+    violations
+}
+
+/// Cross-checks the traced receipts and gas usage against each other and
+/// against the block header, returning a description of every mismatch.
+///
+/// This catches receipts that were traced or encoded incorrectly before they
+/// reach the prover.
+pub fn check_receipts(input: &ProverInput) -> Vec<String> {
+    let mut violations = Vec::new();
+    let mut block_bloom = Bloom::zero();
+    let mut all_decoded = true;
+    let mut block_gas_used = U256::zero();
+    let mut prev_cumulative_gas_used = U256::zero();
+
+    for (i, txn) in input.block_trace.txn_info.iter().enumerate() {
+        let gas_used = U256::from(txn.meta.gas_used);
+        block_gas_used += gas_used;
+
+        let receipt = match Receipt::decode(&txn.meta.new_receipt_trie_node_byte) {
+            Ok(receipt) => receipt,
+            Err(e) => {
+                violations.push(format!("transaction {i} has an undecodable receipt: {e}"));
+                all_decoded = false;
+                continue;
+            }
+        };
+
+        let cumulative_gas_used = receipt.cumulative_gas_used;
+        if cumulative_gas_used < prev_cumulative_gas_used {
+            violations.push(format!(
+                "transaction {i} has cumulative gas used {cumulative_gas_used}, less than the \
+                 {prev_cumulative_gas_used} of the transaction before it"
+            ));
+        } else if all_decoded && cumulative_gas_used - prev_cumulative_gas_used != gas_used {
+            violations.push(format!(
+                "transaction {i} used {gas_used} gas according to its trace, but {} according to \
+                 its receipt",
+                cumulative_gas_used - prev_cumulative_gas_used
+            ));
+        }
+        prev_cumulative_gas_used = cumulative_gas_used;
+
+        let logs_bloom = receipt.compute_logs_bloom();
+        if logs_bloom != receipt.logs_bloom {
+            violations.push(format!(
+                "transaction {i} has a receipt bloom that does not match its logs"
+            ));
+        }
+        block_bloom.accrue_bloom(&logs_bloom);
+    }
+
+    let mut header_bloom = Bloom::zero();
+    for (word, chunk) in input
+        .other_data
+        .b_data
+        .b_meta
+        .block_bloom
+        .iter()
+        .zip(header_bloom.as_fixed_bytes_mut().chunks_exact_mut(32))
+    {
+        word.to_big_endian(chunk);
+    }
+    // Undecodable receipts are already reported, and would make the
+    // comparison meaningless.
+    if all_decoded && block_bloom != header_bloom {
+        violations.push(
+            "logs bloom recomputed from the receipts does not match the block header".to_string(),
+        );
+    }
+
+    let header_gas_used = input.other_data.b_data.b_meta.block_gas_used;
+    if block_gas_used != header_gas_used {
+        violations.push(format!(
+            "transactions used {block_gas_used} gas in total, but the block header says \
+             {header_gas_used}"
+        ));
+    }
+
+    violations
+}
+
+/// Checks the state witness by decoding it, returning why it fails to decode.
+///
+/// The decoder builds the state and storage tries touched by every
+/// transaction from the witness, and fails when a touched account or storage
+/// slot has no proof path in it. Contract code that is read but missing from
+/// the witness makes the decoder panic rather than fail, so that is reported
+/// too.
+pub fn lint_witness(input: ProverInput) -> Option<String> {
+    decoding_failure(|| input.decode().map(drop))
+}
+
+fn decoding_failure(decode: impl FnOnce() -> Result<()>) -> Option<String> {
+    match panic::catch_unwind(AssertUnwindSafe(decode)) {
+        Ok(Ok(())) => None,
+        Ok(Err(e)) => Some(format!("the witness does not decode: {e:#}")),
+        Err(payload) => {
+            let message = payload
+                .downcast_ref::<&str>()
+                .map(|message| message.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_default();
+            Some(format!("the decoder panicked on the witness: {message}"))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use ethereum_types::{Address, BloomInput};
+    use evm_arithmetization::proof::{BlockHashes, BlockMetadata};
+    use rlp::RlpStream;
+    use trace_decoder::{
+        trace_protocol::{
+            BlockTrace, BlockTraceTriePreImages, CombinedPreImages, TrieCompact, TxnInfo, TxnMeta,
+        },
+        types::{BlockLevelData, OtherBlockData},
+    };
+
+    use super::*;
+
+    /// Encodes a legacy receipt with a log from `emitter`, and the bloom of
+    /// that log.
+    fn encode_receipt(cumulative_gas_used: u64, emitter: u8) -> (Vec<u8>, Bloom) {
+        let mut bloom = Bloom::zero();
+        bloom.accrue(BloomInput::Raw(Address::repeat_byte(emitter).as_bytes()));
+        (
+            encode_receipt_with_bloom(cumulative_gas_used, emitter, bloom),
+            bloom,
+        )
+    }
+
+    fn encode_receipt_with_bloom(cumulative_gas_used: u64, emitter: u8, bloom: Bloom) -> Vec<u8> {
+        let mut stream = RlpStream::new_list(4);
+        stream
+            .append(&1u8)
+            .append(&cumulative_gas_used)
+            .append(&bloom);
+        stream.begin_list(1).begin_list(3);
+        stream
+            .append(&Address::repeat_byte(emitter))
+            .append_list::<H256, H256>(&[])
+            .append(&Vec::<u8>::new());
+        stream.out().to_vec()
+    }
+
+    fn txn(receipt: Vec<u8>, gas_used: u64) -> TxnInfo {
+        TxnInfo {
+            traces: HashMap::new(),
+            meta: TxnMeta {
+                byte_code: Vec::new(),
+                new_txn_trie_node_byte: vec![0x80],
+                new_receipt_trie_node_byte: receipt,
+                gas_used,
+            },
+        }
+    }
+
+    /// Returns the input of block 1000 with two transactions, which passes
+    /// every check.
+    fn valid_input() -> ProverInput {
+        let (first, first_bloom) = encode_receipt(21_000, 0x11);
+        let (second, second_bloom) = encode_receipt(71_000, 0x22);
+        let mut block_bloom = first_bloom;
+        block_bloom.accrue_bloom(&second_bloom);
+
+        let mut words = [U256::zero(); 8];
+        for (word, chunk) in words
+            .iter_mut()
+            .zip(block_bloom.as_fixed_bytes().chunks_exact(32))
+        {
+            *word = U256::from_big_endian(chunk);
+        }
+
+        ProverInput {
+            block_trace: BlockTrace {
+                trie_pre_images: BlockTraceTriePreImages::Combined(CombinedPreImages {
+                    compact: TrieCompact(Vec::new()),
+                }),
+                txn_info: vec![txn(first, 21_000), txn(second, 50_000)],
+            },
+            other_data: OtherBlockData {
+                b_data: BlockLevelData {
+                    b_meta: BlockMetadata {
+                        block_number: 1000.into(),
+                        block_gas_used: 71_000.into(),
+                        block_bloom: words,
+                        ..Default::default()
+                    },
+                    b_hashes: BlockHashes {
+                        prev_hashes: (744..1000).map(H256::from_low_u64_be).collect(),
+                        cur_hash: H256::from_low_u64_be(1000),
+                    },
+                    withdrawals: Vec::new(),
+                },
+                checkpoint_state_trie_root: H256::repeat_byte(0xcc),
+            },
+            overridden: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn it_accepts_a_well_formed_input() {
+        assert_eq!(lint_prover_input(&valid_input()), Vec::<String>::new());
+    }
+
+    #[test]
+    fn it_checks_the_previous_block_hashes() {
+        let mut input = valid_input();
+        input.other_data.b_data.b_hashes.prev_hashes.pop();
+        assert_eq!(
+            lint_prover_input(&input),
+            ["expected 256 previous block hashes, got 255"]
+        );
+
+        let mut input = valid_input();
+        input.other_data.b_data.b_hashes.prev_hashes[100] = H256::zero();
+        assert_eq!(
+            lint_prover_input(&input),
+            ["previous block hash 100 is zero but follows non-zero hashes"]
+        );
+
+        // Leading zero hashes are padding, but the parent's never is.
+        let mut input = valid_input();
+        input.other_data.b_data.b_hashes.prev_hashes = vec![H256::zero(); PREV_HASHES_LEN];
+        assert_eq!(
+            lint_prover_input(&input),
+            ["block 1000 is not genesis but its parent hash is zero"]
+        );
+        input.other_data.b_data.b_meta.block_number = 0.into();
+        assert_eq!(lint_prover_input(&input), Vec::<String>::new());
+    }
+
+    #[test]
+    fn it_checks_the_block_and_checkpoint_hashes() {
+        let mut input = valid_input();
+        input.other_data.b_data.b_hashes.cur_hash = H256::zero();
+        input.other_data.checkpoint_state_trie_root = H256::zero();
+        assert_eq!(
+            lint_prover_input(&input),
+            ["block hash is zero", "checkpoint state trie root is zero"]
+        );
+    }
+
+    #[test]
+    fn it_checks_that_transactions_are_encoded() {
+        let mut input = valid_input();
+        input.block_trace.txn_info[1]
+            .meta
+            .new_txn_trie_node_byte
+            .clear();
+        assert_eq!(
+            lint_prover_input(&input),
+            ["transaction 1 has no encoded transaction"]
+        );
+
+        // A missing receipt also fails the receipt checks.
+        let mut input = valid_input();
+        input.block_trace.txn_info[1]
+            .meta
+            .new_receipt_trie_node_byte
+            .clear();
+        let violations = lint_prover_input(&input);
+        assert_eq!(violations[0], "transaction 1 has no encoded receipt");
+        assert!(violations[1].starts_with("transaction 1 has an undecodable receipt"));
+    }
+
+    #[test]
+    fn it_accepts_consistent_receipts() {
+        assert_eq!(check_receipts(&valid_input()), Vec::<String>::new());
+    }
+
+    #[test]
+    fn it_checks_receipt_blooms() {
+        // The second receipt claims the bloom of the first one's log.
+        let mut input = valid_input();
+        let (_, wrong_bloom) = encode_receipt(0, 0x11);
+        input.block_trace.txn_info[1]
+            .meta
+            .new_receipt_trie_node_byte = encode_receipt_with_bloom(71_000, 0x22, wrong_bloom);
+        assert_eq!(
+            check_receipts(&input),
+            ["transaction 1 has a receipt bloom that does not match its logs"]
+        );
+
+        // A log missing from the block header.
+        let mut input = valid_input();
+        input.other_data.b_data.b_meta.block_bloom = [U256::zero(); 8];
+        assert_eq!(
+            check_receipts(&input),
+            ["logs bloom recomputed from the receipts does not match the block header"]
+        );
+    }
+
+    #[test]
+    fn it_checks_gas_used() {
+        let mut input = valid_input();
+        input.block_trace.txn_info[1].meta.gas_used = 40_000;
+        assert_eq!(
+            check_receipts(&input),
+            [
+                "transaction 1 used 40000 gas according to its trace, but 50000 according to its \
+                 receipt",
+                "transactions used 61000 gas in total, but the block header says 71000",
+            ]
+        );
+
+        let mut input = valid_input();
+        input.block_trace.txn_info[1]
+            .meta
+            .new_receipt_trie_node_byte = encode_receipt(20_000, 0x22).0;
+        assert_eq!(
+            check_receipts(&input)[0],
+            "transaction 1 has cumulative gas used 20000, less than the 21000 of the transaction \
+             before it"
+        );
+    }
+
+    #[test]
+    fn it_reports_undecodable_receipts() {
+        let mut input = valid_input();
+        input.block_trace.txn_info[0]
+            .meta
+            .new_receipt_trie_node_byte = vec![0xc0];
+        let violations = check_receipts(&input);
+        // Without the first receipt, the blooms cannot be compared.
+        assert_eq!(violations.len(), 1);
+        assert!(violations[0].starts_with("transaction 0 has an undecodable receipt"));
+    }
+
+    #[test]
+    fn it_reports_decoding_failures() {
+        assert_eq!(decoding_failure(|| Ok(())), None);
+        assert_eq!(
+            decoding_failure(|| Err(anyhow::anyhow!("missing account storage trie"))).unwrap(),
+            "the witness does not decode: missing account storage trie"
+        );
+        let hash = H256::repeat_byte(0xc0);
+        let panicked = decoding_failure(|| panic!("no code for hash {hash:?} in the witness"));
+        assert!(panicked
+            .unwrap()
+            .starts_with("the decoder panicked on the witness: no code for hash 0xc0c0"));
+    }
+}
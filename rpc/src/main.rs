@@ -1,13 +1,114 @@
-use std::io::Write;
+use std::{
+    fs,
+    io::Write,
+    ops::Range,
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
-use anyhow::Result;
+use anyhow::{ensure, Result};
 use clap::Parser;
 use cli::Commands;
-use rpc::{fetch_prover_input, FetchProverInputRequest};
+use cursor::CursorFile;
+use manifest::Manifest;
+use rpc::{
+    block_id::BlockId,
+    compare::{diff_prover_inputs, load_prover_input},
+    fetch_pre_state, fetch_prover_input, fetch_range_commitment,
+    lint::{lint_prover_input, lint_witness},
+    overrides::BlockDataOverrides,
+    signal::cancel_on_ctrl_c,
+    FetchProverInputRequest, PreState,
+};
 
 mod cli;
+mod cursor;
+mod follow;
 mod init;
-mod rpc;
+mod manifest;
+
+/// Fetches the prover inputs of the blocks in `blocks`, one after the other.
+///
+/// Each is written to `<output_dir>/block-<number>.json` as soon as it is
+/// fetched, or, without an output directory, to stdout as an element of a
+/// JSON array. A failed or cancelled fetch still closes the array, so that
+/// the blocks written so far remain readable.
+///
+/// With a cursor file, the blocks up to the one it records are skipped, and
+/// it is updated after every block written. With a manifest, every block
+/// written to the output directory, and the one that failed, is recorded in
+/// it.
+async fn fetch_interval<'a>(
+    blocks: Range<u64>,
+    output_dir: Option<PathBuf>,
+    cursor_file: Option<PathBuf>,
+    manifest: Option<Manifest>,
+    request: impl Fn(BlockId) -> FetchProverInputRequest<'a>,
+) -> Result<()> {
+    let cursor_file = cursor_file.map(|path| CursorFile::new(path, &blocks));
+    let blocks = match &cursor_file {
+        Some(cursor_file) => cursor_file.remaining()?,
+        None => blocks,
+    };
+    if let Some(dir) = &output_dir {
+        fs::create_dir_all(dir)?;
+    } else {
+        std::io::stdout().write_all(b"[")?;
+    }
+    let fetched = write_interval(
+        blocks,
+        output_dir.as_deref(),
+        cursor_file.as_ref(),
+        manifest,
+        request,
+    )
+    .await;
+    if output_dir.is_none() {
+        std::io::stdout().write_all(b"]")?;
+    }
+    fetched
+}
+
+async fn write_interval<'a>(
+    blocks: Range<u64>,
+    output_dir: Option<&Path>,
+    cursor_file: Option<&CursorFile>,
+    mut manifest: Option<Manifest>,
+    request: impl Fn(BlockId) -> FetchProverInputRequest<'a>,
+) -> Result<()> {
+    for number in blocks.clone() {
+        let prover_input = match fetch_prover_input(request(number.into())).await {
+            Ok(prover_input) => prover_input,
+            Err(e) => {
+                if let Some(manifest) = &mut manifest {
+                    manifest.record_failed(number, &e);
+                }
+                return Err(e);
+            }
+        };
+        let bytes = serde_json::to_vec(&prover_input)?;
+        match output_dir {
+            Some(dir) => {
+                let path = dir.join(format!("block-{number}.json"));
+                fs::write(&path, &bytes)?;
+                if let Some(manifest) = &mut manifest {
+                    let block_hash = prover_input.other_data.b_data.b_hashes.cur_hash;
+                    manifest.record_written(number, block_hash, &path, &bytes)?;
+                }
+            }
+            None => {
+                let separator: &[u8] = if number == blocks.start { b"" } else { b"," };
+                let mut stdout = std::io::stdout().lock();
+                stdout.write_all(&[separator, &bytes].concat())?;
+                stdout.flush()?;
+            }
+        }
+        if let Some(cursor_file) = cursor_file {
+            cursor_file.write(number)?;
+        }
+    }
+    Ok(())
+}
 
 #[tokio::main]
 async fn main() -> Result<()> {
@@ -16,17 +117,124 @@ async fn main() -> Result<()> {
 
     match args.command {
         Commands::Fetch {
-            rpc_url,
-            block_number,
-            checkpoint_block_number,
+            rpc,
+            block,
+            block_interval,
+            follow,
+            confirmations,
+            poll_interval,
+            output_dir,
+            cursor_file,
+            manifest,
+            checkpoint,
+            chain,
+            header_defaults,
+            compare_with,
+            pre_state,
+            override_block_data,
         } => {
-            let prover_input = fetch_prover_input(FetchProverInputRequest {
-                rpc_url: &rpc_url,
-                block_number,
-                checkpoint_block_number,
-            })
-            .await?;
+            let reference = compare_with.map(load_prover_input).transpose()?;
+            let pre_state = pre_state.map(PreState::from_file).transpose()?;
+            let overrides = override_block_data
+                .map(BlockDataOverrides::from_file)
+                .transpose()?;
+            let profile = rpc.profile()?;
+            let chain_spec = rpc.chain_spec(chain.as_deref(), &profile)?;
+            let manifest = manifest.map(Manifest::open).transpose()?;
+            let client = rpc.client(&profile)?;
+            let cancel = cancel_on_ctrl_c();
+            let request = |block_id| FetchProverInputRequest {
+                client: &client,
+                block_id,
+                checkpoint_block_id: checkpoint,
+                chain_spec: &chain_spec,
+                header_defaults: &header_defaults,
+                prev_hash_concurrency: rpc.prev_hash_concurrency,
+                cancel: &cancel,
+            };
+
+            if follow {
+                let followed = follow::follow(
+                    &client,
+                    block,
+                    confirmations,
+                    Duration::from_secs(poll_interval),
+                    follow::Output::new(output_dir, manifest)?,
+                    &cancel,
+                    request,
+                )
+                .await;
+                if let Some(summary) = client.metrics_summary() {
+                    eprint!("{summary}");
+                }
+                return followed;
+            }
+            let Some(block) = block else {
+                let blocks = block_interval.expect("enforced by clap");
+                let fetched =
+                    fetch_interval(blocks, output_dir, cursor_file, manifest, request).await;
+                if let Some(summary) = client.metrics_summary() {
+                    eprint!("{summary}");
+                }
+                return fetched;
+            };
+            let prover_input = fetch_prover_input(request(block)).await;
+            if let Some(summary) = client.metrics_summary() {
+                eprint!("{summary}");
+            }
+            let mut prover_input = prover_input?;
+            if let Some(pre_state) = pre_state {
+                pre_state.replace_pre_images(&mut prover_input)?;
+            }
+            if let Some(overrides) = overrides {
+                overrides.apply(&mut prover_input)?;
+            }
             std::io::stdout().write_all(&serde_json::to_vec(&prover_input)?)?;
+
+            if let Some(reference) = reference {
+                let differences = diff_prover_inputs(&prover_input, &reference)?;
+                for difference in &differences {
+                    eprintln!("{difference}");
+                }
+                ensure!(
+                    differences.is_empty(),
+                    "found {} difference(s) from the reference",
+                    differences.len()
+                );
+            }
+        }
+        Commands::PreState { rpc, block, chain } => {
+            let profile = rpc.profile()?;
+            let chain_spec = rpc.chain_spec(chain.as_deref(), &profile)?;
+            let pre_state = fetch_pre_state(&rpc.client(&profile)?, block, &chain_spec).await?;
+            std::io::stdout().write_all(&serde_json::to_vec(&pre_state)?)?;
+        }
+        Commands::CommitRange {
+            rpc,
+            start_block,
+            end_block,
+        } => {
+            let profile = rpc.profile()?;
+            let commitment = fetch_range_commitment(
+                &rpc.client(&profile)?,
+                start_block..=end_block,
+                rpc.prev_hash_concurrency,
+            )
+            .await?;
+            println!("{commitment:?}");
+        }
+        Commands::Lint { input } => {
+            let prover_input = load_prover_input(input)?;
+            let mut violations = lint_prover_input(&prover_input);
+            violations.extend(lint_witness(prover_input));
+            for violation in &violations {
+                println!("{violation}");
+            }
+            ensure!(
+                violations.is_empty(),
+                "found {} violation(s)",
+                violations.len()
+            );
         }
     }
     Ok(())
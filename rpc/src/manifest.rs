@@ -0,0 +1,186 @@
+//! Manifests listing the prover inputs written by interval and follow modes.
+use std::{
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use ethereum_types::H256;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tracing::warn;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+enum Status {
+    Written,
+    Failed,
+}
+
+/// The outcome of fetching a block.
+#[derive(Serialize, Deserialize, Debug)]
+struct Entry {
+    block: u64,
+    status: Status,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    block_hash: Option<H256>,
+    /// The prover input written for the block.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    path: Option<PathBuf>,
+    /// The SHA-256 hash of the prover input file, in hex.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sha256: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+struct Contents {
+    blocks: Vec<Entry>,
+}
+
+/// A JSON file listing every block fetched, with the path and hash of its
+/// prover input, or the error that stopped the fetch.
+///
+/// The file is rewritten after every block. The entries of an existing
+/// manifest are kept, so that a resumed fetch adds to it, and a block fetched
+/// again replaces its entry.
+#[derive(Debug)]
+pub(crate) struct Manifest {
+    path: PathBuf,
+    contents: Contents,
+}
+
+impl Manifest {
+    pub(crate) fn open(path: PathBuf) -> Result<Self> {
+        let contents = match fs::read(&path) {
+            Ok(bytes) => serde_json::from_slice(&bytes)
+                .with_context(|| format!("parsing manifest {}", path.display()))?,
+            Err(e) if e.kind() == ErrorKind::NotFound => Contents::default(),
+            Err(e) => {
+                return Err(e).with_context(|| format!("reading manifest {}", path.display()))
+            }
+        };
+        Ok(Self { path, contents })
+    }
+
+    /// Records that the prover input of a block was written to `path`.
+    pub(crate) fn record_written(
+        &mut self,
+        block: u64,
+        block_hash: H256,
+        path: &Path,
+        bytes: &[u8],
+    ) -> Result<()> {
+        self.record(Entry {
+            block,
+            status: Status::Written,
+            block_hash: Some(block_hash),
+            path: Some(path.to_owned()),
+            sha256: Some(hex::encode(Sha256::digest(bytes))),
+            error: None,
+        })
+    }
+
+    /// Records that fetching or writing a block failed. As this happens while
+    /// handling another error, failing to update the manifest is only logged.
+    pub(crate) fn record_failed(&mut self, block: u64, error: &anyhow::Error) {
+        let recorded = self.record(Entry {
+            block,
+            status: Status::Failed,
+            block_hash: None,
+            path: None,
+            sha256: None,
+            error: Some(format!("{error:#}")),
+        });
+        if let Err(e) = recorded {
+            warn!("Failed to record the failure of block {block}: {e:#}");
+        }
+    }
+
+    fn record(&mut self, entry: Entry) -> Result<()> {
+        let blocks = &mut self.contents.blocks;
+        blocks.retain(|existing| existing.block != entry.block);
+        let index = blocks.partition_point(|existing| existing.block < entry.block);
+        blocks.insert(index, entry);
+
+        let tmp = self.path.with_extension("tmp");
+        fs::write(&tmp, serde_json::to_vec_pretty(&self.contents)?)?;
+        fs::rename(tmp, &self.path)
+            .with_context(|| format!("writing manifest {}", self.path.display()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use anyhow::anyhow;
+    use serde_json::{json, Value};
+
+    use super::*;
+
+    #[test]
+    fn it_lists_blocks_in_order_and_replaces_refetched_ones() {
+        let path = std::env::temp_dir().join(format!("rpc-manifest-{}.json", std::process::id()));
+        let _ = fs::remove_file(&path);
+
+        let mut manifest = Manifest::open(path.clone()).unwrap();
+        manifest
+            .record_written(
+                11,
+                H256::repeat_byte(11),
+                Path::new("out/block-11.json"),
+                b"{}",
+            )
+            .unwrap();
+        manifest.record_failed(12, &anyhow!("header not found"));
+        // A resumed fetch adds to the existing manifest.
+        let mut manifest = Manifest::open(path.clone()).unwrap();
+        manifest
+            .record_written(
+                10,
+                H256::repeat_byte(10),
+                Path::new("out/block-10.json"),
+                b"[]",
+            )
+            .unwrap();
+        manifest
+            .record_written(
+                12,
+                H256::repeat_byte(12),
+                Path::new("out/block-12.json"),
+                b"{}",
+            )
+            .unwrap();
+
+        let written: Value = serde_json::from_slice(&fs::read(&path).unwrap()).unwrap();
+        let blocks = written["blocks"].as_array().unwrap();
+        let numbers: Vec<_> = blocks.iter().map(|entry| entry["block"].clone()).collect();
+        assert_eq!(numbers, [json!(10), json!(11), json!(12)]);
+        assert_eq!(blocks[2]["status"], "written");
+        assert_eq!(blocks[1]["path"], "out/block-11.json");
+        assert_eq!(
+            blocks[1]["sha256"],
+            "44136fa355b3678a1146ad16f7e8649e94fb4fc21fe77e8310c060f61caaff8a"
+        );
+        assert!(blocks[1].get("error").is_none());
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn it_records_failures() {
+        let path =
+            std::env::temp_dir().join(format!("rpc-manifest-failed-{}.json", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let mut manifest = Manifest::open(path.clone()).unwrap();
+        manifest.record_failed(7, &anyhow!("reorged").context("fetching block 7"));
+
+        let written: Value = serde_json::from_slice(&fs::read(&path).unwrap()).unwrap();
+        assert_eq!(
+            written,
+            json!({"blocks": [{"block": 7, "status": "failed", "error": "fetching block 7: reorged"}]})
+        );
+        fs::remove_file(path).unwrap();
+    }
+}
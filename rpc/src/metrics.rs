@@ -0,0 +1,82 @@
+//! Per-method statistics of the RPC calls made by a client.
+use std::{collections::BTreeMap, fmt, sync::Mutex, time::Duration};
+
+/// Statistics of the calls made to a single RPC method.
+#[derive(Debug, Clone, Default)]
+pub struct MethodStats {
+    /// The number of HTTP requests sent, including retries and failovers.
+    /// A batch counts as a single call.
+    pub calls: u64,
+    pub failures: u64,
+    /// The time spent waiting for responses, summed over all calls.
+    pub total_latency: Duration,
+    pub max_latency: Duration,
+}
+
+#[derive(Debug, Default)]
+pub struct RpcMetrics {
+    methods: Mutex<BTreeMap<String, MethodStats>>,
+}
+
+impl RpcMetrics {
+    pub(crate) fn record(&self, method: &str, latency: Duration, success: bool) {
+        let mut methods = self.methods.lock().unwrap();
+        let stats = methods.entry(method.to_owned()).or_default();
+        stats.calls += 1;
+        if !success {
+            stats.failures += 1;
+        }
+        stats.total_latency += latency;
+        stats.max_latency = stats.max_latency.max(latency);
+    }
+
+    /// Returns the statistics recorded so far, by method.
+    pub fn snapshot(&self) -> BTreeMap<String, MethodStats> {
+        self.methods.lock().unwrap().clone()
+    }
+}
+
+/// Formats the statistics as a table, one method per line.
+impl fmt::Display for RpcMetrics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:<28} {:>8} {:>8} {:>10} {:>10} {:>10}",
+            "method", "calls", "failures", "total (s)", "mean (ms)", "max (ms)"
+        )?;
+        for (method, stats) in self.snapshot() {
+            let mean = stats.total_latency / stats.calls.max(1) as u32;
+            writeln!(
+                f,
+                "{:<28} {:>8} {:>8} {:>10.2} {:>10.1} {:>10.1}",
+                method,
+                stats.calls,
+                stats.failures,
+                stats.total_latency.as_secs_f64(),
+                mean.as_secs_f64() * 1000.0,
+                stats.max_latency.as_secs_f64() * 1000.0,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn it_aggregates_calls_per_method() {
+        let metrics = RpcMetrics::default();
+        metrics.record("eth_chainId", Duration::from_millis(10), true);
+        metrics.record("eth_chainId", Duration::from_millis(30), false);
+        metrics.record("eth_blockNumber", Duration::from_millis(5), true);
+
+        let snapshot = metrics.snapshot();
+        let chain_id = &snapshot["eth_chainId"];
+        assert_eq!((chain_id.calls, chain_id.failures), (2, 1));
+        assert_eq!(chain_id.total_latency, Duration::from_millis(40));
+        assert_eq!(chain_id.max_latency, Duration::from_millis(30));
+        assert_eq!(snapshot["eth_blockNumber"].calls, 1);
+    }
+}
@@ -0,0 +1,124 @@
+//! A minimal JSON-RPC node for tests, answering over plain HTTP/1.1.
+use std::{
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+use clap::Parser;
+use reqwest::Url;
+use serde_json::Value;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    net::{TcpListener, TcpStream},
+};
+
+use crate::client::{RpcArgs, RpcClient};
+
+/// How the node answers a request.
+pub(crate) enum Reply {
+    /// A `200 OK` response with this body.
+    Json(Value),
+    /// An empty response with this HTTP status.
+    Status(u16),
+}
+
+type Handler = dyn Fn(&Value) -> Reply + Send + Sync;
+
+/// A node listening on a local port, which answers every request body with
+/// a handler and records the requests it received.
+pub(crate) struct MockNode {
+    pub(crate) url: Url,
+    requests: Arc<Mutex<Vec<Value>>>,
+}
+
+impl MockNode {
+    pub(crate) async fn start(handler: impl Fn(&Value) -> Reply + Send + Sync + 'static) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap())
+            .parse()
+            .unwrap();
+        let requests = Arc::<Mutex<Vec<Value>>>::default();
+        let handler: Arc<Handler> = Arc::new(handler);
+
+        let recorded = Arc::clone(&requests);
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let handler = Arc::clone(&handler);
+                let recorded = Arc::clone(&recorded);
+                tokio::spawn(async move { serve(stream, &*handler, &recorded).await });
+            }
+        });
+
+        Self { url, requests }
+    }
+
+    /// The bodies of the requests received so far.
+    pub(crate) fn requests(&self) -> Vec<Value> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+/// Answers a single request, closing the connection afterwards.
+async fn serve(
+    stream: TcpStream,
+    handler: &Handler,
+    recorded: &Mutex<Vec<Value>>,
+) -> std::io::Result<()> {
+    let mut stream = BufReader::new(stream);
+    let mut content_length = 0;
+    loop {
+        let mut line = String::new();
+        stream.read_line(&mut line).await?;
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse().unwrap_or_default();
+            }
+        }
+    }
+    let mut body = vec![0; content_length];
+    stream.read_exact(&mut body).await?;
+
+    let request: Value = serde_json::from_slice(&body)?;
+    let reply = handler(&request);
+    recorded.lock().unwrap().push(request);
+
+    let (status, body) = match reply {
+        Reply::Json(body) => (200, body.to_string()),
+        Reply::Status(status) => (status, String::new()),
+    };
+    let response = format!(
+        "HTTP/1.1 {status} Mock\r\ncontent-type: application/json\r\ncontent-length: {}\r\n\
+         connection: close\r\n\r\n{body}",
+        body.len()
+    );
+    stream.get_mut().write_all(response.as_bytes()).await?;
+    stream.get_mut().shutdown().await
+}
+
+/// Builds a client for the given endpoints, in order, with additional
+/// command line arguments.
+pub(crate) fn client(endpoints: &[&MockNode], args: &[&str]) -> RpcClient {
+    #[derive(Parser)]
+    struct Cli {
+        #[command(flatten)]
+        rpc: RpcArgs,
+    }
+
+    let urls = endpoints
+        .iter()
+        .flat_map(|node| ["--rpc-url", node.url.as_str()]);
+    let cli = Cli::parse_from(["rpc"].into_iter().chain(urls).chain(args.iter().copied()));
+    cli.rpc.client(&cli.rpc.profile().unwrap()).unwrap()
+}
+
+/// Returns an empty directory for a test to write to.
+pub(crate) fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("rpc-{name}-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
@@ -0,0 +1,114 @@
+//! Counterfactual block data, for studying the prover under modified headers.
+//!
+//! An override file is a JSON object in the serialized form of a prover
+//! input's `other_data`, holding only the fields to change. Objects are merged
+//! field by field, and any other value replaces the fetched one:
+//!
+//! ```json
+//! { "b_data": { "b_meta": { "block_timestamp": "0x65f0a1b3" } } }
+//! ```
+//!
+//! The overridden fields are listed in the prover input's `overridden`, which
+//! marks it as describing a synthetic block.
+use std::{fs::File, io::BufReader, path::Path};
+
+use anyhow::{bail, Context, Result};
+use prover::ProverInput;
+use serde_json::Value;
+
+#[derive(Debug, Clone)]
+pub struct BlockDataOverrides(Value);
+
+impl BlockDataOverrides {
+    /// Reads overrides from a JSON file.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let file = BufReader::new(
+            File::open(path).with_context(|| format!("opening overrides {}", path.display()))?,
+        );
+        let overrides: Value = serde_json::from_reader(file)
+            .with_context(|| format!("deserializing overrides {}", path.display()))?;
+        if !overrides.is_object() {
+            bail!("overrides {} must be a JSON object", path.display());
+        }
+        Ok(Self(overrides))
+    }
+
+    /// Applies the overrides to the block data of a prover input, adding the
+    /// overridden fields to its `overridden`.
+    pub fn apply(&self, prover_input: &mut ProverInput) -> Result<()> {
+        let mut other_data = serde_json::to_value(&prover_input.other_data)?;
+        merge("", &mut other_data, &self.0, &mut prover_input.overridden)?;
+        prover_input.other_data = serde_path_to_error::deserialize(other_data)
+            .context("applying overrides to the block data")?;
+        Ok(())
+    }
+}
+
+/// Merges `patch` into `target`, pushing the path of every replaced value to
+/// `overridden`.
+///
+/// Fails on fields that `target` does not have, so that a misspelled field is
+/// not silently ignored.
+fn merge(
+    path: &str,
+    target: &mut Value,
+    patch: &Value,
+    overridden: &mut Vec<String>,
+) -> Result<()> {
+    match (target, patch) {
+        (Value::Object(target), Value::Object(patch)) => {
+            for (key, patch) in patch {
+                let path = if path.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{path}.{key}")
+                };
+                match target.get_mut(key) {
+                    Some(target) => merge(&path, target, patch, overridden)?,
+                    None => bail!("the block data has no field {path}"),
+                }
+            }
+        }
+        (target, patch) => {
+            *target = patch.clone();
+            overridden.push(path.to_owned());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn it_merges_overrides_field_by_field() {
+        let mut target = json!({
+            "b_data": { "b_meta": { "block_timestamp": "0x1", "block_gaslimit": "0x2" } },
+            "checkpoint_state_trie_root": "0x3",
+        });
+        let mut overridden = Vec::new();
+        merge(
+            "",
+            &mut target,
+            &json!({ "b_data": { "b_meta": { "block_timestamp": "0x10" } } }),
+            &mut overridden,
+        )
+        .unwrap();
+
+        assert_eq!(
+            target,
+            json!({
+                "b_data": { "b_meta": { "block_timestamp": "0x10", "block_gaslimit": "0x2" } },
+                "checkpoint_state_trie_root": "0x3",
+            })
+        );
+        assert_eq!(overridden, ["b_data.b_meta.block_timestamp"]);
+
+        let misspelled = json!({ "b_data": { "b_meta": { "block_timestmap": "0x10" } } });
+        assert!(merge("", &mut target, &misspelled, &mut overridden).is_err());
+    }
+}
@@ -0,0 +1,157 @@
+//! Named RPC endpoint profiles.
+//!
+//! A profiles file maps names to vetted endpoint settings, so that a team can
+//! share them and select one with `--rpc-profile <NAME>` instead of repeating
+//! long flag strings:
+//!
+//! ```json
+//! {
+//!   "mainnet-erigon": { "url": "http://erigon:8545", "fallback_urls": ["http://erigon-2:8545"] },
+//!   "devnet": { "url": "http://devnet:8545", "chain_spec": { "chain_id": 1337 } },
+//!   "hosted": {
+//!     "url": "https://rpc.example.com",
+//!     "auth": { "bearer": "<TOKEN>" },
+//!     "transport": { "http_version": "http2", "proxy_url": "socks5h://proxy:1080" }
+//!   }
+//! }
+//! ```
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    num::{NonZeroU32, NonZeroUsize},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Context, Result};
+use reqwest::Url;
+use serde::{Deserialize, Deserializer};
+
+use crate::{chain_spec::ChainSpec, client::HttpVersion};
+
+/// Settings for a single RPC endpoint.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct RpcProfile {
+    /// The RPC URL.
+    #[serde(deserialize_with = "deserialize_url")]
+    pub url: Url,
+    /// Endpoints serving the same chain, tried in order when the active one
+    /// fails.
+    #[serde(default, deserialize_with = "deserialize_urls")]
+    pub fallback_urls: Vec<Url>,
+    /// The maximum number of requests per second the endpoint accepts.
+    /// Overridden by `--max-rps`.
+    #[serde(default)]
+    pub max_rps: Option<NonZeroU32>,
+    /// The maximum number of requests per JSON-RPC batch the endpoint
+    /// accepts. Overridden by `--rpc-batch-size`.
+    #[serde(default)]
+    pub batch_size: Option<NonZeroUsize>,
+    /// The chain spec of the chain served by the endpoint. Overridden by
+    /// `--chain`.
+    #[serde(default)]
+    pub chain_spec: Option<ChainSpec>,
+    /// How to authenticate to the endpoints. Overridden by
+    /// `--rpc-auth-token`, `--rpc-auth-basic` and `--jwt-secret`.
+    #[serde(default)]
+    pub auth: Option<ProfileAuth>,
+    /// How to connect to the endpoints.
+    #[serde(default)]
+    pub transport: TransportProfile,
+}
+
+/// The credentials of an endpoint, as one of `{"bearer": "<TOKEN>"}`,
+/// `{"basic": "<USER>:<PASSWORD>"}` or `{"jwt_secret": "<PATH>"}`.
+#[derive(Deserialize, Clone)]
+#[serde(rename_all = "snake_case")]
+pub enum ProfileAuth {
+    /// A bearer token sent in the `Authorization` header.
+    Bearer(String),
+    /// Credentials for HTTP basic authentication, as `USER:PASSWORD`.
+    Basic(String),
+    /// A file holding the hex-encoded secret of a JWT-authenticated port.
+    JwtSecret(PathBuf),
+}
+
+impl std::fmt::Debug for ProfileAuth {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProfileAuth::Bearer(_) => f.write_str("Bearer(..)"),
+            ProfileAuth::Basic(_) => f.write_str("Basic(..)"),
+            ProfileAuth::JwtSecret(path) => f.debug_tuple("JwtSecret").field(path).finish(),
+        }
+    }
+}
+
+/// Connection settings of an endpoint.
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct TransportProfile {
+    /// The HTTP version to speak to the endpoints. Overridden by
+    /// `--rpc-http-version`.
+    #[serde(default)]
+    pub http_version: Option<HttpVersion>,
+    /// The proxy to send requests through. Overridden by `--proxy-url`.
+    #[serde(default, deserialize_with = "deserialize_optional_url")]
+    pub proxy_url: Option<Url>,
+}
+
+impl RpcProfile {
+    pub fn new(url: Url, fallback_urls: Vec<Url>) -> Self {
+        Self {
+            url,
+            fallback_urls,
+            max_rps: None,
+            batch_size: None,
+            chain_spec: None,
+            auth: None,
+            transport: TransportProfile::default(),
+        }
+    }
+}
+
+fn deserialize_url<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Url, D::Error> {
+    String::deserialize(deserializer)?
+        .parse()
+        .map_err(serde::de::Error::custom)
+}
+
+fn deserialize_optional_url<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Url>, D::Error> {
+    Option::<String>::deserialize(deserializer)?
+        .map(|url| url.parse().map_err(serde::de::Error::custom))
+        .transpose()
+}
+
+fn deserialize_urls<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<Url>, D::Error> {
+    Vec::<String>::deserialize(deserializer)?
+        .into_iter()
+        .map(|url| url.parse().map_err(serde::de::Error::custom))
+        .collect()
+}
+
+/// Reads the profile with the given name from a JSON profiles file.
+pub fn load_profile<P: AsRef<Path>>(path: P, name: &str) -> Result<RpcProfile> {
+    let path = path.as_ref();
+    let file =
+        File::open(path).with_context(|| format!("opening RPC profiles {}", path.display()))?;
+    let des = &mut serde_json::Deserializer::from_reader(&file);
+    let mut profiles: BTreeMap<String, RpcProfile> = serde_path_to_error::deserialize(des)
+        .with_context(|| format!("deserializing RPC profiles {}", path.display()))?;
+
+    let known = profiles.keys().cloned().collect::<Vec<_>>().join(", ");
+    let profile = profiles.remove(name).ok_or_else(|| {
+        anyhow!(
+            "no RPC profile named {name:?} in {} (known profiles: {known})",
+            path.display()
+        )
+    })?;
+    if let Some(chain_spec) = &profile.chain_spec {
+        chain_spec
+            .validate()
+            .with_context(|| format!("validating chain spec of RPC profile {name:?}"))?;
+    }
+
+    Ok(profile)
+}
@@ -0,0 +1,88 @@
+//! A token bucket limiting the rate of outbound requests.
+use std::{num::NonZeroU32, sync::Mutex, time::Duration};
+
+use tokio::time::Instant;
+
+/// Limits requests to a steady rate, while allowing bursts of up to one
+/// second's worth of requests after a quiet period.
+///
+/// Implemented as the generic cell rate algorithm, which is equivalent to a
+/// token bucket but only needs to track a single timestamp.
+#[derive(Debug)]
+pub(crate) struct RateLimiter {
+    /// The time it takes to earn one token.
+    interval: Duration,
+    /// How far ahead of the theoretical arrival time a request may be sent,
+    /// i.e., the size of the bucket.
+    burst: Duration,
+    /// The theoretical arrival time of the next request, if requests were
+    /// sent at exactly the steady rate.
+    tat: Mutex<Instant>,
+}
+
+impl RateLimiter {
+    pub(crate) fn per_second(rate: NonZeroU32) -> Self {
+        let interval = Duration::from_secs(1) / rate.get();
+        Self {
+            interval,
+            burst: interval * (rate.get() - 1),
+            tat: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Waits until a request may be sent.
+    pub(crate) async fn acquire(&self) {
+        let send_at = {
+            let mut tat = self.tat.lock().unwrap();
+            let now = Instant::now();
+            let arrival = (*tat).max(now);
+            *tat = arrival + self.interval;
+            arrival
+                .checked_sub(self.burst)
+                .map_or(now, |earliest| earliest.max(now))
+        };
+        tokio::time::sleep_until(send_at).await;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn it_allows_bursts_and_then_spaces_requests() {
+        let limiter = RateLimiter::per_second(NonZeroU32::new(4).unwrap());
+        let start = Instant::now();
+
+        // A second's worth of requests go out at once.
+        for _ in 0..4 {
+            limiter.acquire().await;
+        }
+        assert_eq!(start.elapsed(), Duration::ZERO);
+        // After that, requests are spaced at the steady rate.
+        for i in 1..=3 {
+            limiter.acquire().await;
+            assert_eq!(start.elapsed(), Duration::from_millis(250 * i));
+        }
+
+        // A quiet period refills the bucket, but no further than a burst.
+        tokio::time::advance(Duration::from_secs(5)).await;
+        let resumed = Instant::now();
+        for _ in 0..4 {
+            limiter.acquire().await;
+        }
+        assert_eq!(resumed.elapsed(), Duration::ZERO);
+        limiter.acquire().await;
+        assert_eq!(resumed.elapsed(), Duration::from_millis(250));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn it_does_not_burst_at_one_request_per_second() {
+        let limiter = RateLimiter::per_second(NonZeroU32::MIN);
+        let start = Instant::now();
+        for i in 0..3 {
+            limiter.acquire().await;
+            assert_eq!(start.elapsed(), Duration::from_secs(i));
+        }
+    }
+}
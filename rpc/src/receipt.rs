@@ -0,0 +1,188 @@
+//! Decoding of the receipts included in a block trace.
+use ethereum_types::{Address, Bloom, BloomInput, H256, U256};
+use rlp::{DecoderError, Rlp};
+
+/// The type of OP-stack deposit transactions, whose receipts carry extra
+/// fields.
+const DEPOSIT_TX_TYPE: u8 = 0x7e;
+
+/// Whether an encoded transaction, as stored in the transaction trie, is an
+/// OP-stack deposit transaction.
+pub(crate) fn is_deposit_transaction(bytes: &[u8]) -> bool {
+    typed_payload(bytes).first() == Some(&DEPOSIT_TX_TYPE)
+}
+
+/// Strips the RLP string that may wrap a typed (EIP-2718) trie entry.
+fn typed_payload(bytes: &[u8]) -> &[u8] {
+    let rlp = Rlp::new(bytes);
+    if rlp.is_data() && bytes.first().is_some_and(|&b| b >= 0x80) {
+        rlp.data().unwrap_or(bytes)
+    } else {
+        bytes
+    }
+}
+
+/// A log emitted by a transaction.
+#[derive(Debug)]
+pub(crate) struct Log {
+    pub(crate) address: Address,
+    pub(crate) topics: Vec<H256>,
+}
+
+/// The fields of a transaction receipt needed to cross-check a block header.
+#[derive(Debug)]
+pub(crate) struct Receipt {
+    pub(crate) cumulative_gas_used: U256,
+    pub(crate) logs_bloom: Bloom,
+    pub(crate) logs: Vec<Log>,
+}
+
+impl Receipt {
+    /// Decodes a receipt as stored in the receipt trie.
+    ///
+    /// Legacy receipts are a bare RLP list. Typed (EIP-2718) receipts are the
+    /// type byte followed by the RLP list, and may additionally be wrapped in
+    /// an RLP string. Receipts of OP-stack deposit transactions (type `0x7e`)
+    /// may also carry the deposit nonce and receipt version, which are
+    /// ignored.
+    pub(crate) fn decode(bytes: &[u8]) -> Result<Self, DecoderError> {
+        let rlp = Rlp::new(bytes);
+        if rlp.is_list() {
+            return Self::decode_fields(&rlp, 4);
+        }
+
+        match typed_payload(bytes).split_first() {
+            Some((&DEPOSIT_TX_TYPE, fields)) => Self::decode_fields(&Rlp::new(fields), 6),
+            Some((&tx_type, fields)) if tx_type < 0x80 => Self::decode_fields(&Rlp::new(fields), 4),
+            _ => Err(DecoderError::Custom("expected a typed receipt")),
+        }
+    }
+
+    /// Decodes the leading fields of a receipt list of between 4 and
+    /// `max_fields` items.
+    fn decode_fields(rlp: &Rlp, max_fields: usize) -> Result<Self, DecoderError> {
+        if !(4..=max_fields).contains(&rlp.item_count()?) {
+            return Err(DecoderError::RlpIncorrectListLen);
+        }
+
+        let logs = rlp
+            .at(3)?
+            .iter()
+            .map(|log| {
+                Ok(Log {
+                    address: log.val_at(0)?,
+                    topics: log.list_at(1)?,
+                })
+            })
+            .collect::<Result<_, DecoderError>>()?;
+
+        Ok(Self {
+            cumulative_gas_used: rlp.val_at(1)?,
+            logs_bloom: rlp.val_at(2)?,
+            logs,
+        })
+    }
+
+    /// Recomputes the logs bloom from the receipt's logs.
+    pub(crate) fn compute_logs_bloom(&self) -> Bloom {
+        let mut bloom = Bloom::zero();
+        for log in &self.logs {
+            bloom.accrue(BloomInput::Raw(log.address.as_bytes()));
+            for topic in &log.topics {
+                bloom.accrue(BloomInput::Raw(topic.as_bytes()));
+            }
+        }
+        bloom
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use rlp::RlpStream;
+
+    use super::*;
+
+    /// Encodes a receipt with a single log, and a bloom matching that log.
+    fn encode_legacy_receipt() -> Vec<u8> {
+        let address = Address::repeat_byte(0x11);
+        let topic = H256::repeat_byte(0x22);
+        let mut bloom = Bloom::zero();
+        bloom.accrue(BloomInput::Raw(address.as_bytes()));
+        bloom.accrue(BloomInput::Raw(topic.as_bytes()));
+
+        let mut stream = RlpStream::new_list(4);
+        stream.append(&1u8).append(&21000u64).append(&bloom);
+        stream.begin_list(1).begin_list(3);
+        stream
+            .append(&address)
+            .append_list(&[topic])
+            .append(&vec![0u8; 4]);
+        stream.out().to_vec()
+    }
+
+    #[test]
+    fn it_decodes_legacy_receipts() {
+        let receipt = Receipt::decode(&encode_legacy_receipt()).unwrap();
+        assert_eq!(receipt.cumulative_gas_used, U256::from(21000));
+        assert_eq!(receipt.logs.len(), 1);
+        assert_eq!(receipt.compute_logs_bloom(), receipt.logs_bloom);
+    }
+
+    #[test]
+    fn it_decodes_typed_receipts() {
+        let typed = [&[0x02][..], &encode_legacy_receipt()].concat();
+        let wrapped = rlp::encode(&typed).to_vec();
+
+        for bytes in [typed, wrapped] {
+            let receipt = Receipt::decode(&bytes).unwrap();
+            assert_eq!(receipt.compute_logs_bloom(), receipt.logs_bloom);
+        }
+    }
+
+    #[test]
+    fn it_decodes_deposit_receipts() {
+        let legacy = encode_legacy_receipt();
+        let fields = Rlp::new(&legacy);
+        // Pre-Canyon receipts add the deposit nonce, later ones also the
+        // receipt version.
+        for extra in [&[][..], &[7u64], &[7, 1]] {
+            let mut stream = RlpStream::new_list(4 + extra.len());
+            for field in fields.iter() {
+                stream.append_raw(field.as_raw(), 1);
+            }
+            for value in extra {
+                stream.append(value);
+            }
+            let bytes = [&[DEPOSIT_TX_TYPE][..], &stream.out()].concat();
+            let receipt = Receipt::decode(&bytes).unwrap();
+            assert_eq!(receipt.cumulative_gas_used, U256::from(21000));
+        }
+
+        // Other receipts do not carry the extra fields.
+        let mut stream = RlpStream::new_list(5);
+        for field in fields.iter() {
+            stream.append_raw(field.as_raw(), 1);
+        }
+        stream.append(&7u64);
+        assert!(Receipt::decode(&[&[0x02][..], &stream.out()].concat()).is_err());
+    }
+
+    #[test]
+    fn it_recognizes_deposit_transactions() {
+        let deposit = [&[DEPOSIT_TX_TYPE][..], &rlp::encode_list::<u64, _>(&[1, 2])].concat();
+        assert!(is_deposit_transaction(&deposit));
+        assert!(is_deposit_transaction(&rlp::encode(&deposit)));
+        assert!(!is_deposit_transaction(&[0x02, 0xc0]));
+        assert!(!is_deposit_transaction(&rlp::encode_list::<u64, _>(&[
+            1, 2
+        ])));
+        assert!(!is_deposit_transaction(&[]));
+    }
+
+    #[test]
+    fn it_rejects_invalid_receipts() {
+        assert!(Receipt::decode(&[]).is_err());
+        assert!(Receipt::decode(&[0xc0]).is_err());
+        assert!(Receipt::decode(&rlp::encode(&"not a receipt")).is_err());
+    }
+}
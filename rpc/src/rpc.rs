@@ -1,17 +1,34 @@
-use anyhow::{Context, Result};
+use std::{
+    fs::File, io::BufReader, num::NonZeroUsize, ops::RangeInclusive, path::Path, time::Duration,
+};
+
+use anyhow::{bail, ensure, Context, Result};
 use ethereum_types::{Address, Bloom, H256, U256};
 use evm_arithmetization::proof::{BlockHashes, BlockMetadata};
-use futures::{stream::FuturesOrdered, TryStreamExt};
+use futures::{stream, StreamExt, TryStreamExt};
 use prover::ProverInput;
-use reqwest::IntoUrl;
-use serde::Deserialize;
+use rlp::RlpStream;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
 use thiserror::Error;
 use tokio::try_join;
+use tokio_util::sync::CancellationToken;
 use trace_decoder::{
     trace_protocol::{BlockTrace, BlockTraceTriePreImages, TxnInfo},
     types::{BlockLevelData, OtherBlockData},
 };
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
+
+use crate::{
+    block_id::BlockId,
+    chain_spec::{ChainSpec, PREV_HASHES_LEN},
+    client::RpcClient,
+    commitment,
+    error::RpcError,
+    header_defaults::HeaderDefaults,
+    lint::check_receipts,
+    trie::ordered_trie_root,
+};
 
 #[derive(Deserialize, Debug)]
 #[serde(rename_all = "snake_case")]
@@ -62,148 +79,316 @@ impl TryFrom<JerigonTraceResponse> for BlockTrace {
 }
 
 impl JerigonTraceResponse {
-    /// Fetches the block trace for the given block number.
-    async fn fetch<U: IntoUrl>(rpc_url: U, block_number: u64) -> Result<Self> {
-        let client = reqwest::Client::new();
-        let block_number_hex = format!("0x{:x}", block_number);
-        info!("Fetching block trace for block {}", block_number_hex);
-
-        let response = client
-            .post(rpc_url)
-            .json(&serde_json::json!({
-                "jsonrpc": "2.0",
-                "method": "debug_traceBlockByNumber",
-                "params": [&block_number_hex, {"tracer": "zeroTracer"}],
-                "id": 1,
-            }))
-            .send()
-            .await
-            .context("fetching debug_traceBlockByNumber")?;
-
-        let bytes = response.bytes().await?;
-        let des = &mut serde_json::Deserializer::from_slice(&bytes);
-        let parsed = serde_path_to_error::deserialize(des)
-            .context("deserializing debug_traceBlockByNumber")?;
+    /// Fetches the block trace for the given block using the given tracer.
+    async fn fetch(client: &RpcClient, block_id: BlockId, tracer: &str) -> Result<Self> {
+        let (method, block_param) = block_id.rpc_method_and_param("debug_traceBlockBy");
+        info!("Fetching block trace for block {}", block_id);
 
-        Ok(parsed)
+        client
+            .request(&method, json!([block_param, {"tracer": tracer}]))
+            .await
     }
 }
 
 #[derive(Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
 struct EthGetBlockByNumberResult {
-    base_fee_per_gas: U256,
+    base_fee_per_gas: Option<U256>,
     difficulty: U256,
     gas_limit: U256,
     gas_used: U256,
     hash: H256,
     logs_bloom: Bloom,
     miner: Address,
-    mix_hash: H256,
+    mix_hash: Option<H256>,
     number: U256,
+    parent_beacon_block_root: Option<H256>,
     parent_hash: H256,
     state_root: H256,
     timestamp: U256,
-    withdrawals: Vec<Withdrawal>,
+    withdrawals: Option<Vec<Withdrawal>>,
+    withdrawals_root: Option<H256>,
 }
 
 #[derive(Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
 struct Withdrawal {
+    index: U256,
+    validator_index: U256,
     address: Address,
     amount: U256,
 }
 
+impl Withdrawal {
+    /// The EIP-4895 encoding of the withdrawal, as stored in the withdrawals
+    /// trie.
+    fn rlp_bytes(&self) -> Vec<u8> {
+        let mut stream = RlpStream::new_list(4);
+        stream
+            .append(&self.index)
+            .append(&self.validator_index)
+            .append(&self.address)
+            .append(&self.amount);
+        stream.out().to_vec()
+    }
+}
+
 impl From<Withdrawal> for (Address, U256) {
     fn from(v: Withdrawal) -> Self {
         (v.address, v.amount)
     }
 }
 
-/// The response from the `eth_getBlockByNumber` RPC method.
+/// The response from the `eth_getBlockByNumber` (or `eth_getBlockByHash`) RPC
+/// method.
 #[derive(Deserialize, Debug)]
 struct EthGetBlockByNumberResponse {
     result: EthGetBlockByNumberResult,
 }
 
+/// The response from the `eth_getBlockByNumber` (or `eth_getBlockByHash`) RPC
+/// method for a block that may not exist.
+#[derive(Deserialize, Debug)]
+struct EthGetOptionalBlockResponse {
+    /// Required, though it may be null, so that an error response is not
+    /// mistaken for a missing block.
+    #[serde(deserialize_with = "Option::deserialize")]
+    result: Option<EthGetBlockByNumberResult>,
+}
+
+/// How many times a missing target block is retried before giving up.
+const NOT_FOUND_RETRIES: u32 = 5;
+
+/// The delay before the first retry of a missing target block, doubled on
+/// every retry.
+const NOT_FOUND_BACKOFF: Duration = Duration::from_millis(500);
+
+/// How far past the node's head a block may be and still be considered
+/// recent rather than nonexistent. Behind a load balancer, the backend that
+/// reports the head may lag the one that announced the block.
+const RECENT_BLOCKS: u64 = 2;
+
 impl EthGetBlockByNumberResponse {
-    /// Fetches the block metadata for the given block number.
-    async fn fetch<U: IntoUrl>(rpc_url: U, block_number: u64) -> Result<Self> {
-        let client = reqwest::Client::new();
-        let block_number_hex = format!("0x{:x}", block_number);
-        info!("Fetching block metadata for block {}", block_number_hex);
-
-        let response = client
-            .post(rpc_url)
-            .json(&serde_json::json!({
-                "jsonrpc": "2.0",
-                "method": "eth_getBlockByNumber",
-                "params": [&block_number_hex, false],
-                "id": 1,
-            }))
-            .send()
-            .await
-            .context("fetching eth_getBlockByNumber")?;
+    /// Fetches the block metadata for the block to prove.
+    ///
+    /// The block may have been announced so recently that the node, or the
+    /// backend of a load-balanced endpoint that serves the request, does not
+    /// know it yet. A missing block is therefore retried with backoff, unless
+    /// its number is well past the node's head, in which case it does not
+    /// exist yet and the fetch fails right away.
+    async fn fetch_target(client: &RpcClient, block_id: BlockId) -> Result<Self> {
+        let (method, block_param) = block_id.rpc_method_and_param("eth_getBlockBy");
+        info!("Fetching block metadata for block {}", block_id);
+
+        let mut retries = 0;
+        let mut backoff = NOT_FOUND_BACKOFF;
+        loop {
+            let response: EthGetOptionalBlockResponse = client
+                .request(&method, json!([block_param.clone(), false]))
+                .await?;
+            if let Some(result) = response.result {
+                let resolved = Self { result };
+                resolved.log_resolution(block_id);
+                return Ok(resolved);
+            }
+
+            match block_id {
+                BlockId::Number(number) => {
+                    let head = EthBlockNumberResponse::fetch(client).await?.result.as_u64();
+                    if number > head + RECENT_BLOCKS {
+                        return Err(RpcError::BeyondHead { number, head }.into());
+                    }
+                }
+                BlockId::Tag(_) => return Err(RpcError::BlockNotFound(block_id).into()),
+                BlockId::Hash(_) => {}
+            }
+            if retries == NOT_FOUND_RETRIES {
+                return Err(anyhow::Error::from(RpcError::BlockNotFound(block_id))
+                    .context(format!("gave up after {NOT_FOUND_RETRIES} retries")));
+            }
+            warn!("block {block_id} not found, retrying in {backoff:?}");
+            tokio::time::sleep(backoff).await;
+            retries += 1;
+            backoff *= 2;
+        }
+    }
+
+    /// Fetches the block metadata for the given block.
+    async fn fetch(client: &RpcClient, block_id: BlockId) -> Result<Self> {
+        let (method, block_param) = block_id.rpc_method_and_param("eth_getBlockBy");
+        info!("Fetching block metadata for block {}", block_id);
 
-        let bytes = response.bytes().await?;
-        let des = &mut serde_json::Deserializer::from_slice(&bytes);
-        let parsed =
-            serde_path_to_error::deserialize(des).context("deserializing eth_getBlockByNumber")?;
+        client.request(&method, json!([block_param, false])).await
+    }
+
+    /// Fetches the block metadata for each of the given block numbers in a
+    /// single batch.
+    async fn fetch_batch(client: &RpcClient, block_numbers: Vec<u64>) -> Result<Vec<Self>> {
+        info!(
+            "Fetching block metadata for blocks {:?}..={:?}",
+            block_numbers.first(),
+            block_numbers.last()
+        );
+
+        let params = block_numbers
+            .into_iter()
+            .map(|block_number| {
+                let (_, block_param) =
+                    BlockId::Number(block_number).rpc_method_and_param("eth_getBlockBy");
+                json!([block_param, false])
+            })
+            .collect();
+        client.batch_request("eth_getBlockByNumber", params).await
+    }
 
-        Ok(parsed)
+    /// Fetches the blocks in `range` in order, requesting at most
+    /// `concurrency` headers, or batches of headers if the client batches
+    /// requests, at once.
+    async fn fetch_range(
+        client: &RpcClient,
+        range: RangeInclusive<u64>,
+        concurrency: NonZeroUsize,
+    ) -> Result<Vec<EthGetBlockByNumberResult>> {
+        match client.batch_size() {
+            Some(batch_size) => {
+                let batches: Vec<Vec<u64>> = range
+                    .collect::<Vec<_>>()
+                    .chunks(batch_size.get())
+                    .map(<[u64]>::to_vec)
+                    .collect();
+                stream::iter(batches)
+                    .map(|batch| Self::fetch_batch(client, batch))
+                    .buffered(concurrency.get())
+                    .map_ok(|responses| stream::iter(responses).map(anyhow::Ok))
+                    .try_flatten()
+                    .map_ok(|response| response.result)
+                    .try_collect()
+                    .await
+            }
+            None => {
+                stream::iter(range)
+                    .map(|block_number| Self::fetch(client, block_number.into()))
+                    .buffered(concurrency.get())
+                    .map_ok(|response| response.result)
+                    .try_collect()
+                    .await
+            }
+        }
     }
 
-    async fn fetch_previous_block_hashes<U: IntoUrl + Copy>(
-        rpc_url: U,
+    /// Fetches the hashes of the `window` blocks preceding the given block.
+    ///
+    /// The result is always [`PREV_HASHES_LEN`] long; hashes outside of the
+    /// window (or before genesis) are zeroed. A window longer than that is
+    /// clamped to it.
+    ///
+    /// Ancestors are looked up by number, so the fetched headers are checked
+    /// to form a single chain ending at `parent_hash`. This turns a reorg
+    /// during the fetch into an error rather than a mix of hashes from
+    /// different forks. The headers are only cached once they pass this
+    /// check, so that a reorg cannot leave headers of another fork cached
+    /// under the block.
+    async fn fetch_previous_block_hashes(
+        client: &RpcClient,
         block_number: u64,
+        parent_hash: H256,
+        window: usize,
+        concurrency: NonZeroUsize,
     ) -> Result<Vec<H256>> {
         if block_number == 0 {
-            return Ok(vec![H256::default(); 256]);
+            return Ok(vec![H256::default(); PREV_HASHES_LEN]);
         }
 
-        let mut hashes = Vec::with_capacity(256);
+        let mut hashes = Vec::with_capacity(PREV_HASHES_LEN);
 
-        let padding_delta = block_number as i64 - 256;
-        if padding_delta < 0 {
-            let default = H256::default();
-            for _ in 0..padding_delta.abs() {
-                hashes.push(default);
+        // Chain specs are validated when read, but their fields are public.
+        let window = window.min(PREV_HASHES_LEN);
+        let start = block_number.saturating_sub(window as u64);
+        let padding = PREV_HASHES_LEN - (block_number - start) as usize;
+        hashes.extend(std::iter::repeat_n(H256::default(), padding));
+
+        let staged = client.staged();
+        let ancestors = Self::fetch_range(&staged, start..=block_number - 1, concurrency).await?;
+
+        let children = ancestors
+            .iter()
+            .skip(1)
+            .map(|child| (child.number, child.parent_hash))
+            .chain(std::iter::once((block_number.into(), parent_hash)));
+        for (ancestor, (child_number, child_parent_hash)) in ancestors.iter().zip(children) {
+            if ancestor.hash != child_parent_hash {
+                return Err(RpcError::Reorged {
+                    child: child_number,
+                    ancestor: ancestor.number,
+                    ancestor_hash: ancestor.hash,
+                }
+                .into());
             }
         }
 
-        // Every block response includes the _parent_ hash along with its hash, so we
-        // can just fetch half the blocks to acquire all hashes for the range.
-        let start = block_number.saturating_sub(256);
-        let mut futs: FuturesOrdered<_> = (start..=block_number)
-            .step_by(2)
-            .map(|block_number| Self::fetch(rpc_url, block_number))
-            .collect();
+        staged.commit_staged();
+        hashes.extend(ancestors.iter().map(|ancestor| ancestor.hash));
 
-        while let Some(response) = futs.try_next().await? {
-            // Ignore hash of the current block.
-            if response.result.number == block_number.into() {
-                hashes.push(response.result.parent_hash);
-                continue;
-            }
+        Ok(hashes)
+    }
 
-            // Ignore the parent of the start block.
-            if response.result.number != start.into() {
-                hashes.push(response.result.parent_hash);
-            }
+    /// Fetches the checkpoint block, whose state root becomes the
+    /// `checkpoint_state_trie_root` of the block proof.
+    async fn fetch_checkpoint(client: &RpcClient, block_id: BlockId) -> Result<Self> {
+        info!("Fetching checkpoint block {}", block_id);
+        let checkpoint = Self::fetch(client, block_id).await?;
+        checkpoint.log_resolution(block_id);
+        Ok(checkpoint)
+    }
 
-            hashes.push(response.result.hash);
+    /// Logs the number and hash of a block requested by hash or tag, which
+    /// are otherwise only known to the node.
+    fn log_resolution(&self, block_id: BlockId) {
+        if !matches!(block_id, BlockId::Number(_)) {
+            info!(
+                "Resolved block {block_id} to block {} ({:?})",
+                self.result.number, self.result.hash
+            );
         }
+    }
+}
 
-        Ok(hashes)
+/// Checks that the checkpoint precedes the block to prove and, if it is
+/// within the block's previous hashes, that it is one of its ancestors.
+///
+/// Checkpoints further back are accepted without checking their ancestry,
+/// which would mean fetching every header in between.
+fn check_checkpoint(
+    block_number: u64,
+    prev_hashes: &[H256],
+    window: usize,
+    checkpoint_number: u64,
+    checkpoint_hash: H256,
+) -> Result<()> {
+    // Genesis has nothing to precede it, and can only be its own checkpoint.
+    if block_number == 0 {
+        return Ok(());
     }
+    ensure!(
+        checkpoint_number < block_number,
+        "checkpoint block {checkpoint_number} does not precede block {block_number}"
+    );
 
-    async fn fetch_checkpoint_state_trie_root<U: IntoUrl + Copy>(
-        rpc_url: U,
-        block_number: u64,
-    ) -> Result<H256> {
-        let res = Self::fetch(rpc_url, block_number).await?;
-        Ok(res.result.state_root)
+    let distance = block_number - checkpoint_number;
+    if distance as usize > window.min(prev_hashes.len()) {
+        debug!(
+            "checkpoint block {checkpoint_number} is {distance} blocks back, beyond the \
+             previous hashes; not checking its ancestry"
+        );
+        return Ok(());
     }
+    let ancestor_hash = prev_hashes[prev_hashes.len() - distance as usize];
+    ensure!(
+        ancestor_hash == checkpoint_hash,
+        "checkpoint block {checkpoint_number} ({checkpoint_hash:?}) is not an ancestor of block \
+         {block_number}, whose ancestor at that height is {ancestor_hash:?}"
+    );
+    Ok(())
 }
 
 /// The response from the `eth_chainId` RPC method.
@@ -214,27 +399,23 @@ struct EthChainIdResponse {
 
 impl EthChainIdResponse {
     /// Fetches the chain id.
-    async fn fetch<U: IntoUrl>(rpc_url: U) -> Result<Self> {
-        let client = reqwest::Client::new();
+    async fn fetch(client: &RpcClient) -> Result<Self> {
         info!("Fetching chain id");
 
-        let response = client
-            .post(rpc_url)
-            .json(&serde_json::json!({
-                "jsonrpc": "2.0",
-                "method": "eth_chainId",
-                "params": [],
-                "id": 1,
-            }))
-            .send()
-            .await
-            .context("fetching eth_chainId")?;
+        client.request("eth_chainId", json!([])).await
+    }
+}
 
-        let bytes = response.bytes().await?;
-        let des = &mut serde_json::Deserializer::from_slice(&bytes);
-        let parsed = serde_path_to_error::deserialize(des).context("deserializing eth_chainId")?;
+/// The response from the `eth_blockNumber` RPC method.
+#[derive(Deserialize, Debug)]
+struct EthBlockNumberResponse {
+    result: U256,
+}
 
-        Ok(parsed)
+impl EthBlockNumberResponse {
+    /// Fetches the number of the node's head block.
+    async fn fetch(client: &RpcClient) -> Result<Self> {
+        client.request("eth_blockNumber", json!([])).await
     }
 }
 
@@ -243,41 +424,170 @@ impl EthChainIdResponse {
 /// Contains the necessary data to construct the `OtherBlockData` struct.
 struct RpcBlockMetadata {
     block_by_number: EthGetBlockByNumberResponse,
-    chain_id: EthChainIdResponse,
+    chain_id: U256,
     prev_hashes: Vec<H256>,
     checkpoint_state_trie_root: H256,
 }
 
 impl RpcBlockMetadata {
-    async fn fetch(rpc_url: &str, block_number: u64, checkpoint_block_number: u64) -> Result<Self> {
-        let (block_result, chain_id_result, prev_hashes, checkpoint_state_trie_root) = try_join!(
-            EthGetBlockByNumberResponse::fetch(rpc_url, block_number),
-            EthChainIdResponse::fetch(rpc_url),
-            EthGetBlockByNumberResponse::fetch_previous_block_hashes(rpc_url, block_number),
-            EthGetBlockByNumberResponse::fetch_checkpoint_state_trie_root(
-                rpc_url,
-                checkpoint_block_number
-            )
+    /// Fetches the remaining metadata for an already resolved block.
+    async fn fetch(
+        client: &RpcClient,
+        block_by_number: EthGetBlockByNumberResponse,
+        chain_id: U256,
+        checkpoint_block_id: BlockId,
+        chain_spec: &ChainSpec,
+        prev_hash_concurrency: NonZeroUsize,
+    ) -> Result<Self> {
+        let block_number = block_by_number.result.number.as_u64();
+        let (prev_hashes, checkpoint) = try_join!(
+            EthGetBlockByNumberResponse::fetch_previous_block_hashes(
+                client,
+                block_number,
+                block_by_number.result.parent_hash,
+                chain_spec.blockhash_window,
+                prev_hash_concurrency
+            ),
+            EthGetBlockByNumberResponse::fetch_checkpoint(client, checkpoint_block_id)
+        )?;
+        check_checkpoint(
+            block_number,
+            &prev_hashes,
+            chain_spec.blockhash_window,
+            checkpoint.result.number.as_u64(),
+            checkpoint.result.hash,
         )?;
 
         Ok(Self {
-            block_by_number: block_result,
-            chain_id: chain_id_result,
+            block_by_number,
+            chain_id,
             prev_hashes,
-            checkpoint_state_trie_root,
+            checkpoint_state_trie_root: checkpoint.result.state_root,
         })
     }
-}
 
-impl From<RpcBlockMetadata> for OtherBlockData {
-    fn from(
-        RpcBlockMetadata {
+    /// Builds the [`OtherBlockData`] for the block, checking the optional
+    /// header fields against the fork schedule of the chain spec.
+    ///
+    /// Missing fields are substituted from `header_defaults` where allowed.
+    fn into_other_block_data(
+        self,
+        chain_spec: &ChainSpec,
+        header_defaults: &HeaderDefaults,
+    ) -> Result<OtherBlockData> {
+        let RpcBlockMetadata {
             block_by_number,
             chain_id,
             prev_hashes,
             checkpoint_state_trie_root,
-        }: RpcBlockMetadata,
-    ) -> Self {
+        } = self;
+
+        let number = block_by_number.result.number.as_u64();
+        let block_base_fee = match (
+            chain_spec.is_london(number),
+            block_by_number.result.base_fee_per_gas,
+        ) {
+            (true, Some(base_fee)) => base_fee,
+            (false, None) => U256::zero(),
+            (true, None) => match header_defaults.base_fee() {
+                Some(base_fee) => {
+                    warn!("block {number} has no base fee, using {base_fee}");
+                    base_fee
+                }
+                None => bail!(
+                    "block {number} has no base fee, but the chain spec places it after London; \
+                     set `london_block` in a chain spec to fetch pre-London blocks"
+                ),
+            },
+            (false, Some(_)) => {
+                bail!("block {number} has a base fee but the chain spec places it before London")
+            }
+        };
+
+        let timestamp = block_by_number.result.timestamp.as_u64();
+        let withdrawals = match (
+            chain_spec.is_shanghai(timestamp),
+            block_by_number.result.withdrawals,
+        ) {
+            (true, Some(withdrawals)) => withdrawals,
+            (false, None) => Vec::new(),
+            (false, Some(withdrawals)) if withdrawals.is_empty() => withdrawals,
+            (true, None) if header_defaults.withdrawals() => {
+                warn!("block {number} has no withdrawals, assuming none");
+                Vec::new()
+            }
+            (true, None) => bail!("block {number} is post-Shanghai but has no withdrawals"),
+            (false, Some(_)) => {
+                bail!("block {number} has withdrawals but the chain spec places it before Shanghai")
+            }
+        };
+        match (
+            chain_spec.is_cancun(timestamp),
+            block_by_number.result.parent_beacon_block_root,
+        ) {
+            (false, None) => {}
+            (false, Some(_)) => bail!(
+                "block {number} has a parent beacon block root but the chain spec places it \
+                 before Cancun"
+            ),
+            (true, None) => {
+                bail!("block {number} is post-Cancun but has no parent beacon block root")
+            }
+            (true, Some(_)) => {
+                bail!("block {number} is past the Cancun fork, which the prover does not support")
+            }
+        }
+        // This also catches withdrawals wrongly assumed to be empty in
+        // tolerant mode.
+        if let Some(withdrawals_root) = block_by_number.result.withdrawals_root {
+            let computed = ordered_trie_root(withdrawals.iter().map(Withdrawal::rlp_bytes));
+            ensure!(
+                computed == withdrawals_root,
+                "block {number} has withdrawals root {withdrawals_root:?}, but its withdrawals \
+                 hash to {computed:?}"
+            );
+        }
+
+        let gas_limit = block_by_number.result.gas_limit;
+        let gas_used = block_by_number.result.gas_used;
+        ensure!(
+            gas_used <= gas_limit,
+            "block {number} uses {gas_used} gas, above its gas limit of {gas_limit}"
+        );
+        if let Some(max_gas_limit) = chain_spec.max_gas_limit {
+            ensure!(
+                gas_limit <= max_gas_limit.into(),
+                "block {number} has a gas limit of {gas_limit}, above the chain spec's \
+                 max_gas_limit of {max_gas_limit}"
+            );
+        }
+
+        let difficulty = block_by_number.result.difficulty;
+        let block_random = if chain_spec.is_merged(number) {
+            ensure!(
+                difficulty.is_zero(),
+                "block {number} has difficulty {difficulty} but the chain spec places it after \
+                 the merge"
+            );
+            match block_by_number.result.mix_hash {
+                Some(mix_hash) => mix_hash,
+                None => match header_defaults.mix_hash() {
+                    Some(mix_hash) => {
+                        warn!("block {number} has no mix hash, using {mix_hash:?}");
+                        mix_hash
+                    }
+                    None => bail!("block {number} has no mix hash"),
+                },
+            }
+        } else {
+            // Before the merge, the opcode that became `PREVRANDAO` returned
+            // the block's difficulty, and the mix hash is a proof-of-work
+            // artifact.
+            let mut random = H256::zero();
+            difficulty.to_big_endian(random.as_bytes_mut());
+            random
+        };
+
         let mut bloom = [U256::zero(); 8];
 
         for (i, word) in block_by_number
@@ -294,22 +604,17 @@ impl From<RpcBlockMetadata> for OtherBlockData {
             block_beneficiary: block_by_number.result.miner,
             block_timestamp: block_by_number.result.timestamp,
             block_number: block_by_number.result.number,
-            block_difficulty: block_by_number.result.difficulty,
-            block_random: block_by_number.result.mix_hash,
-            block_gaslimit: block_by_number.result.gas_limit,
-            block_chain_id: chain_id.result,
-            block_base_fee: block_by_number.result.base_fee_per_gas,
-            block_gas_used: block_by_number.result.gas_used,
+            block_difficulty: difficulty,
+            block_random,
+            block_gaslimit: gas_limit,
+            block_chain_id: chain_id,
+            block_base_fee,
+            block_gas_used: gas_used,
             block_bloom: bloom,
         };
 
-        let withdrawals = block_by_number
-            .result
-            .withdrawals
-            .into_iter()
-            .map(|w| w.into())
-            .collect();
-        Self {
+        let withdrawals = withdrawals.into_iter().map(|w| w.into()).collect();
+        Ok(OtherBlockData {
             b_data: BlockLevelData {
                 b_meta: block_metadata,
                 b_hashes: BlockHashes {
@@ -319,34 +624,507 @@ impl From<RpcBlockMetadata> for OtherBlockData {
                 withdrawals,
             },
             checkpoint_state_trie_root,
+        })
+    }
+}
+
+/// The state a block executes on, without the block's transaction traces.
+///
+/// This is the witness part of a block trace, for executors that produce
+/// their own traces.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PreState {
+    pub block_number: U256,
+    pub block_hash: H256,
+    pub parent_hash: H256,
+    /// The pre-images of the state and storage tries touched by the block,
+    /// including contract code, in the format of a block trace's
+    /// `trie_pre_images`.
+    pub trie_pre_images: BlockTraceTriePreImages,
+}
+
+impl PreState {
+    /// Reads a pre-state, as written by `rpc pre-state`, from a JSON file.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let file = BufReader::new(
+            File::open(path).with_context(|| format!("opening pre-state {}", path.display()))?,
+        );
+        let des = &mut serde_json::Deserializer::from_reader(file);
+        serde_path_to_error::deserialize(des)
+            .with_context(|| format!("deserializing pre-state {}", path.display()))
+    }
+
+    /// Replaces the trie pre-images of a prover input with this pre-state's,
+    /// keeping its transaction traces.
+    ///
+    /// Fails if the prover input is for another block than the pre-state.
+    pub fn replace_pre_images(self, prover_input: &mut ProverInput) -> Result<()> {
+        let block_hash = prover_input.other_data.b_data.b_hashes.cur_hash;
+        ensure!(
+            block_hash == self.block_hash,
+            "the pre-state is for block {} ({:?}), but the fetched block {} is {block_hash:?}",
+            self.block_number,
+            self.block_hash,
+            prover_input.get_block_number()
+        );
+        prover_input.block_trace.trie_pre_images = self.trie_pre_images;
+
+        Ok(())
+    }
+}
+
+/// Fetches the [`PreState`] of a block.
+///
+/// The node only serves the witness along with the transaction traces, so
+/// the whole block is traced and the traces are dropped.
+pub async fn fetch_pre_state(
+    client: &RpcClient,
+    block_id: BlockId,
+    chain_spec: &ChainSpec,
+) -> Result<PreState> {
+    let header = EthGetBlockByNumberResponse::fetch_target(client, block_id)
+        .await?
+        .result;
+    chain_spec.check_supported(header.number.as_u64(), header.timestamp.as_u64())?;
+    let trace =
+        JerigonTraceResponse::fetch(client, BlockId::Hash(header.hash), &chain_spec.tracer).await?;
+
+    Ok(PreState {
+        block_number: header.number,
+        block_hash: header.hash,
+        parent_hash: header.parent_hash,
+        trie_pre_images: BlockTrace::try_from(trace)?.trie_pre_images,
+    })
+}
+
+/// Fetches the number of the node's head block.
+pub async fn fetch_head(client: &RpcClient) -> Result<u64> {
+    Ok(EthBlockNumberResponse::fetch(client).await?.result.as_u64())
+}
+
+/// Fetches the headers of a range of blocks and computes their
+/// [hash-chain commitment](crate::commitment), checking that they form a
+/// single chain.
+pub async fn fetch_range_commitment(
+    client: &RpcClient,
+    blocks: RangeInclusive<u64>,
+    concurrency: NonZeroUsize,
+) -> Result<H256> {
+    let (start, end) = blocks.into_inner();
+    ensure!(start <= end, "the range {start}..={end} is empty");
+    let headers = fetch_chain(client, start, end, concurrency).await?;
+    Ok(fold_commitment(&headers))
+}
+
+/// Computes the [hash-chain commitment](crate::commitment) over the blocks
+/// with the given hashes, numbered consecutively from `start`, checking that
+/// they are still the node's blocks at these heights.
+///
+/// This ties a commitment to blocks fetched earlier, which may have been
+/// reorganized out of the chain since.
+pub async fn fetch_commitment_of_blocks(
+    client: &RpcClient,
+    start: u64,
+    hashes: &[H256],
+    concurrency: NonZeroUsize,
+) -> Result<H256> {
+    ensure!(!hashes.is_empty(), "no blocks to commit to");
+    let end = start
+        .checked_add(hashes.len() as u64 - 1)
+        .context("the blocks extend past the last block number")?;
+    let headers = fetch_chain(client, start, end, concurrency).await?;
+    for (header, hash) in headers.iter().zip(hashes) {
+        ensure!(
+            header.hash == *hash,
+            "block {} is {:?} on the node, but {hash:?} was fetched before; the chain \
+             reorganized in the meantime",
+            header.number,
+            header.hash
+        );
+    }
+    Ok(fold_commitment(&headers))
+}
+
+/// Fetches the headers of the blocks `start..=end`, checking that they form a
+/// single chain.
+async fn fetch_chain(
+    client: &RpcClient,
+    start: u64,
+    end: u64,
+    concurrency: NonZeroUsize,
+) -> Result<Vec<EthGetBlockByNumberResult>> {
+    let headers =
+        EthGetBlockByNumberResponse::fetch_range(client, start..=end, concurrency).await?;
+
+    for (parent, child) in headers.iter().zip(headers.iter().skip(1)) {
+        if child.parent_hash != parent.hash {
+            return Err(RpcError::Reorged {
+                child: child.number,
+                ancestor: parent.number,
+                ancestor_hash: parent.hash,
+            }
+            .into());
         }
     }
+    Ok(headers)
+}
+
+fn fold_commitment(headers: &[EthGetBlockByNumberResult]) -> H256 {
+    headers.iter().fold(H256::zero(), |c, header| {
+        commitment::fold(c, header.hash, header.state_root)
+    })
 }
 
 pub struct FetchProverInputRequest<'a> {
-    pub rpc_url: &'a str,
-    pub block_number: u64,
-    pub checkpoint_block_number: u64,
+    pub client: &'a RpcClient,
+    pub block_id: BlockId,
+    pub checkpoint_block_id: BlockId,
+    pub chain_spec: &'a ChainSpec,
+    pub header_defaults: &'a HeaderDefaults,
+    pub prev_hash_concurrency: NonZeroUsize,
+    /// Aborts the fetch when cancelled.
+    pub cancel: &'a CancellationToken,
 }
 
 pub async fn fetch_prover_input(
     FetchProverInputRequest {
-        rpc_url,
-        block_number,
-        checkpoint_block_number,
+        client,
+        block_id,
+        checkpoint_block_id,
+        chain_spec,
+        header_defaults,
+        prev_hash_concurrency,
+        cancel,
     }: FetchProverInputRequest<'_>,
 ) -> Result<ProverInput> {
-    let (trace_result, rpc_block_metadata) = try_join!(
-        JerigonTraceResponse::fetch(rpc_url, block_number),
-        RpcBlockMetadata::fetch(rpc_url, block_number, checkpoint_block_number),
-    )?;
+    let fetch = async {
+        // Resolve the target block first and trace it by hash, so that the
+        // trace and the header are guaranteed to describe the same block even
+        // if the chain reorganizes while we fetch.
+        let chain_id = async {
+            match chain_spec.chain_id {
+                Some(chain_id) => Ok(chain_id.into()),
+                None => EthChainIdResponse::fetch(client).await.map(|r| r.result),
+            }
+        };
+        let (block_by_number, chain_id) = try_join!(
+            EthGetBlockByNumberResponse::fetch_target(client, block_id),
+            chain_id
+        )?;
+        chain_spec.check_supported(
+            block_by_number.result.number.as_u64(),
+            block_by_number.result.timestamp.as_u64(),
+        )?;
+        let trace_block_id = BlockId::Hash(block_by_number.result.hash);
+
+        // Everything else is pinned to the resolved block, so it can be
+        // served from the cache.
+        let client = &client.scoped(chain_id, block_by_number.result.hash);
+        try_join!(
+            JerigonTraceResponse::fetch(client, trace_block_id, &chain_spec.tracer),
+            RpcBlockMetadata::fetch(
+                client,
+                block_by_number,
+                chain_id,
+                checkpoint_block_id,
+                chain_spec,
+                prev_hash_concurrency
+            ),
+        )
+    };
+    let (trace_result, rpc_block_metadata) = tokio::select! {
+        biased;
+        () = cancel.cancelled() => return Err(RpcError::Cancelled(block_id).into()),
+        fetched = fetch => fetched?,
+    };
 
     debug!("Got block result: {:?}", rpc_block_metadata.block_by_number);
     debug!("Got trace result: {:?}", trace_result);
     debug!("Got chain_id: {:?}", rpc_block_metadata.chain_id);
 
-    Ok(ProverInput {
+    let prover_input = ProverInput {
         block_trace: trace_result.try_into()?,
-        other_data: rpc_block_metadata.into(),
-    })
+        other_data: rpc_block_metadata.into_other_block_data(chain_spec, header_defaults)?,
+        overridden: Vec::new(),
+    };
+
+    chain_spec.check_transactions(&prover_input)?;
+    let violations = check_receipts(&prover_input);
+    ensure!(
+        violations.is_empty(),
+        "block {} failed validation:\n{}",
+        prover_input.get_block_number(),
+        violations.join("\n")
+    );
+
+    Ok(prover_input)
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    };
+
+    use serde_json::Value;
+
+    use super::*;
+    use crate::mock_node::{self, MockNode, Reply};
+
+    fn header_json(number: u64, hash: H256, parent_hash: H256) -> Value {
+        json!({
+            "difficulty": "0x0",
+            "gasLimit": "0x1c9c380",
+            "gasUsed": "0x0",
+            "hash": hash,
+            "logsBloom": Bloom::zero(),
+            "miner": Address::zero(),
+            "number": format!("{number:#x}"),
+            "parentHash": parent_hash,
+            "stateRoot": H256::zero(),
+            "timestamp": "0x0",
+        })
+    }
+
+    /// Builds the metadata of a post-Shanghai block from its header, with
+    /// `fields` overriding the header's.
+    fn block_metadata(fields: Value) -> RpcBlockMetadata {
+        let mut header = header_json(1, H256::repeat_byte(1), H256::zero());
+        header["baseFeePerGas"] = json!("0x7");
+        header["mixHash"] = json!(H256::zero());
+        header["withdrawals"] = json!([]);
+        for (name, value) in fields.as_object().unwrap() {
+            header[name] = value.clone();
+        }
+        RpcBlockMetadata {
+            block_by_number: serde_json::from_value(json!({ "result": header })).unwrap(),
+            chain_id: U256::one(),
+            prev_hashes: vec![H256::zero(); PREV_HASHES_LEN],
+            checkpoint_state_trie_root: H256::zero(),
+        }
+    }
+
+    #[test]
+    fn it_checks_header_fields_against_the_chain_spec() {
+        let spec = ChainSpec::default();
+        let defaults = HeaderDefaults::default();
+        let error = |fields: Value, spec: &ChainSpec| {
+            block_metadata(fields)
+                .into_other_block_data(spec, &defaults)
+                .unwrap_err()
+                .to_string()
+        };
+
+        let data = block_metadata(json!({}))
+            .into_other_block_data(&spec, &defaults)
+            .unwrap();
+        assert_eq!(data.b_data.b_meta.block_base_fee, 7.into());
+
+        assert!(error(json!({"gasUsed": "0x1c9c381"}), &spec).contains("above its gas limit"));
+        let bounded = ChainSpec {
+            max_gas_limit: Some(1_000_000),
+            ..ChainSpec::default()
+        };
+        assert!(error(json!({}), &bounded).contains("max_gas_limit"));
+
+        let root = json!({"parentBeaconBlockRoot": H256::zero()});
+        assert!(error(root.clone(), &spec).contains("before Cancun"));
+        let cancun = ChainSpec {
+            cancun_timestamp: Some(0),
+            ..ChainSpec::default()
+        };
+        assert!(error(root, &cancun).contains("past the Cancun fork"));
+        assert!(error(json!({}), &cancun).contains("no parent beacon block root"));
+    }
+
+    #[test]
+    fn it_checks_the_checkpoint_against_previous_hashes() {
+        let prev_hashes: Vec<_> = (0..PREV_HASHES_LEN as u64)
+            .map(H256::from_low_u64_be)
+            .collect();
+        // Block 1000's previous hashes end with the hash of block 999.
+        let hash_of = |number: u64| H256::from_low_u64_be(PREV_HASHES_LEN as u64 + number - 1000);
+
+        assert!(check_checkpoint(1000, &prev_hashes, 256, 999, hash_of(999)).is_ok());
+        assert!(check_checkpoint(1000, &prev_hashes, 256, 744, hash_of(744)).is_ok());
+        assert!(check_checkpoint(1000, &prev_hashes, 256, 999, H256::zero()).is_err());
+        // Beyond the window, the ancestry is not checked.
+        assert!(check_checkpoint(1000, &prev_hashes, 256, 10, H256::zero()).is_ok());
+        assert!(check_checkpoint(1000, &prev_hashes, 100, 800, H256::zero()).is_ok());
+        assert!(check_checkpoint(1000, &prev_hashes, 256, 1000, H256::zero()).is_err());
+        assert!(check_checkpoint(0, &prev_hashes, 256, 0, H256::zero()).is_ok());
+    }
+
+    #[tokio::test]
+    async fn it_does_not_mistake_node_errors_for_missing_blocks() {
+        let hash = H256::from_low_u64_be(1010);
+        let failing = MockNode::start(|request| {
+            Reply::Json(json!({
+                "jsonrpc": "2.0",
+                "id": request["id"],
+                "error": { "code": -32000, "message": "internal error" },
+            }))
+        })
+        .await;
+        let healthy = MockNode::start(move |request| {
+            Reply::Json(json!({
+                "jsonrpc": "2.0",
+                "id": request["id"],
+                "result": header_json(10, hash, H256::from_low_u64_be(1009)),
+            }))
+        })
+        .await;
+        let cache_dir = mock_node::temp_dir("block-error");
+        let cache_args = ["--cache-dir", cache_dir.to_str().unwrap()];
+
+        // The error is returned, rather than retried as a missing block, and
+        // it is not cached as one.
+        let client = mock_node::client(&[&failing], &cache_args).scoped(U256::one(), hash);
+        for attempt in 1..=2 {
+            let error = EthGetBlockByNumberResponse::fetch_target(&client, BlockId::Hash(hash))
+                .await
+                .unwrap_err();
+            assert!(matches!(
+                RpcError::find(&error),
+                Some(RpcError::Node { code: -32000, .. })
+            ));
+            assert_eq!(failing.requests().len(), attempt);
+        }
+
+        // With another endpoint, the error fails over.
+        let client =
+            mock_node::client(&[&failing, &healthy], &cache_args).scoped(U256::one(), hash);
+        let block = EthGetBlockByNumberResponse::fetch_target(&client, BlockId::Hash(hash))
+            .await
+            .unwrap();
+        assert_eq!(block.result.hash, hash);
+        assert_eq!(failing.requests().len(), 3);
+
+        std::fs::remove_dir_all(cache_dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn it_does_not_cache_ancestors_of_another_fork() {
+        let hash_of = |number: u64| H256::from_low_u64_be(1000 + number);
+        let reorged = Arc::new(AtomicBool::new(true));
+        let node = MockNode::start({
+            let reorged = Arc::clone(&reorged);
+            move |request| {
+                let number = request["params"][0].as_str().unwrap();
+                let number = u64::from_str_radix(number.trim_start_matches("0x"), 16).unwrap();
+                // While reorged, block 8 is on another fork than block 9.
+                let hash = match number {
+                    8 if reorged.load(Ordering::Relaxed) => H256::from_low_u64_be(8),
+                    _ => hash_of(number),
+                };
+                Reply::Json(json!({
+                    "jsonrpc": "2.0",
+                    "id": request["id"],
+                    "result": header_json(number, hash, hash_of(number - 1)),
+                }))
+            }
+        })
+        .await;
+        let cache_dir = mock_node::temp_dir("ancestor-cache");
+        let client = mock_node::client(&[&node], &["--cache-dir", cache_dir.to_str().unwrap()])
+            .scoped(U256::one(), hash_of(10));
+        let fetch = || {
+            EthGetBlockByNumberResponse::fetch_previous_block_hashes(
+                &client,
+                10,
+                hash_of(9),
+                3,
+                NonZeroUsize::MIN,
+            )
+        };
+
+        let error = fetch().await.unwrap_err();
+        assert!(matches!(
+            error.downcast_ref(),
+            Some(RpcError::Reorged { .. })
+        ));
+        assert_eq!(node.requests().len(), 3);
+
+        // Once the node has settled, the ancestors are fetched again rather
+        // than read from the cache.
+        reorged.store(false, Ordering::Relaxed);
+        let hashes = fetch().await.unwrap();
+        assert_eq!(
+            hashes[PREV_HASHES_LEN - 3..],
+            [hash_of(7), hash_of(8), hash_of(9)]
+        );
+        assert_eq!(node.requests().len(), 6);
+
+        // The checked ancestors are cached.
+        assert_eq!(fetch().await.unwrap(), hashes);
+        assert_eq!(node.requests().len(), 6);
+
+        std::fs::remove_dir_all(cache_dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn it_clamps_the_blockhash_window() {
+        let hash_of = |number: u64| H256::from_low_u64_be(1000 + number);
+        let node = MockNode::start(move |request| {
+            let number = request["params"][0].as_str().unwrap();
+            let number = u64::from_str_radix(number.trim_start_matches("0x"), 16).unwrap();
+            Reply::Json(json!({
+                "jsonrpc": "2.0",
+                "id": request["id"],
+                "result": header_json(number, hash_of(number), hash_of(number - 1)),
+            }))
+        })
+        .await;
+        let client = mock_node::client(&[&node], &[]);
+
+        let hashes = EthGetBlockByNumberResponse::fetch_previous_block_hashes(
+            &client,
+            300,
+            hash_of(299),
+            1000,
+            NonZeroUsize::new(16).unwrap(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(hashes.len(), PREV_HASHES_LEN);
+        assert_eq!(hashes[0], hash_of(44));
+        assert_eq!(hashes[PREV_HASHES_LEN - 1], hash_of(299));
+        assert_eq!(node.requests().len(), PREV_HASHES_LEN);
+    }
+
+    #[tokio::test]
+    async fn it_commits_only_to_the_blocks_fetched_before() {
+        let hash_of = |number: u64| H256::from_low_u64_be(1000 + number);
+        let node = MockNode::start(move |request| {
+            let number = request["params"][0].as_str().unwrap();
+            let number = u64::from_str_radix(number.trim_start_matches("0x"), 16).unwrap();
+            Reply::Json(json!({
+                "jsonrpc": "2.0",
+                "id": request["id"],
+                "result": header_json(number, hash_of(number), hash_of(number - 1)),
+            }))
+        })
+        .await;
+        let client = mock_node::client(&[&node], &[]);
+
+        let commitment = fetch_range_commitment(&client, 5..=7, NonZeroUsize::MIN)
+            .await
+            .unwrap();
+        let hashes = [hash_of(5), hash_of(6), hash_of(7)];
+        assert_eq!(
+            fetch_commitment_of_blocks(&client, 5, &hashes, NonZeroUsize::MIN)
+                .await
+                .unwrap(),
+            commitment
+        );
+
+        let reorged = [hash_of(5), H256::from_low_u64_be(6), hash_of(7)];
+        assert!(
+            fetch_commitment_of_blocks(&client, 5, &reorged, NonZeroUsize::MIN)
+                .await
+                .is_err()
+        );
+    }
 }
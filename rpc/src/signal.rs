@@ -0,0 +1,26 @@
+//! Cancellation on Ctrl-C, shared by the binaries that fetch blocks.
+use tokio_util::sync::CancellationToken;
+use tracing::warn;
+
+/// Returns a token that is cancelled on the first Ctrl-C, so that the block
+/// being fetched or proved is abandoned while the work already done stays
+/// intact. A second Ctrl-C exits immediately.
+///
+/// Must be called from within a Tokio runtime.
+pub fn cancel_on_ctrl_c() -> CancellationToken {
+    let cancel = CancellationToken::new();
+    tokio::spawn({
+        let cancel = cancel.clone();
+        async move {
+            if tokio::signal::ctrl_c().await.is_err() {
+                return;
+            }
+            warn!("Cancelling; press Ctrl-C again to exit immediately");
+            cancel.cancel();
+            if tokio::signal::ctrl_c().await.is_ok() {
+                std::process::exit(130);
+            }
+        }
+    });
+    cancel
+}
@@ -0,0 +1,174 @@
+//! Root computation for Merkle Patricia tries built from scratch, such as the
+//! withdrawals trie of a block.
+use ethereum_types::H256;
+use rlp::RlpStream;
+use tiny_keccak::{Hasher, Keccak};
+
+/// Hashes bytes with Keccak-256, as used for Ethereum block, transaction and
+/// trie node hashes.
+pub fn keccak(bytes: &[u8]) -> H256 {
+    let mut output = [0u8; 32];
+    let mut hasher = Keccak::v256();
+    hasher.update(bytes);
+    hasher.finalize(&mut output);
+    H256(output)
+}
+
+/// Computes the root of a trie whose keys are the RLP-encoded indices of the
+/// given values, as used for the transactions, receipts and withdrawals tries.
+pub(crate) fn ordered_trie_root<I, V>(values: I) -> H256
+where
+    I: IntoIterator<Item = V>,
+    V: AsRef<[u8]>,
+{
+    trie_root(
+        values
+            .into_iter()
+            .enumerate()
+            .map(|(i, value)| (rlp::encode(&i).to_vec(), value)),
+    )
+}
+
+/// Computes the root of the trie holding the given key-value pairs.
+pub(crate) fn trie_root<I, K, V>(pairs: I) -> H256
+where
+    I: IntoIterator<Item = (K, V)>,
+    K: AsRef<[u8]>,
+    V: AsRef<[u8]>,
+{
+    let mut items: Vec<(Vec<u8>, V)> = pairs
+        .into_iter()
+        .map(|(key, value)| (to_nibbles(key.as_ref()), value))
+        .collect();
+    items.sort_by(|a, b| a.0.cmp(&b.0));
+    items.dedup_by(|a, b| a.0 == b.0);
+
+    keccak(&encode_node(&items, 0))
+}
+
+fn to_nibbles(bytes: &[u8]) -> Vec<u8> {
+    bytes.iter().flat_map(|b| [b >> 4, b & 0x0f]).collect()
+}
+
+/// Hex-prefix encodes a nibble path, as defined in the yellow paper.
+fn hex_prefix(nibbles: &[u8], leaf: bool) -> Vec<u8> {
+    let flag = if leaf { 2 } else { 0 };
+    let mut encoded = Vec::with_capacity(nibbles.len() / 2 + 1);
+    let rest = if nibbles.len() % 2 == 1 {
+        encoded.push(((flag + 1) << 4) | nibbles[0]);
+        &nibbles[1..]
+    } else {
+        encoded.push(flag << 4);
+        nibbles
+    };
+    encoded.extend(rest.chunks_exact(2).map(|pair| (pair[0] << 4) | pair[1]));
+    encoded
+}
+
+/// RLP-encodes the node holding `items`, whose keys all share their first
+/// `depth` nibbles. `items` must be sorted by key.
+fn encode_node<V: AsRef<[u8]>>(items: &[(Vec<u8>, V)], depth: usize) -> Vec<u8> {
+    let mut stream = RlpStream::new();
+    match items {
+        [] => {
+            stream.append_empty_data();
+        }
+        [(key, value)] => {
+            stream.begin_list(2);
+            stream.append(&hex_prefix(&key[depth..], true));
+            stream.append(&value.as_ref());
+        }
+        _ => {
+            // Items are sorted, so the prefix shared by all of them is the one
+            // shared by the first and the last.
+            let (first, last) = (&items[0].0, &items[items.len() - 1].0);
+            let shared = first[depth..]
+                .iter()
+                .zip(&last[depth..])
+                .take_while(|(a, b)| a == b)
+                .count();
+
+            if shared > 0 {
+                stream.begin_list(2);
+                stream.append(&hex_prefix(&first[depth..depth + shared], false));
+                append_child(&mut stream, &encode_node(items, depth + shared));
+            } else {
+                stream.begin_list(17);
+                let (terminal, mut rest) = match items.split_first() {
+                    Some((item, rest)) if item.0.len() == depth => (Some(item), rest),
+                    _ => (None, items),
+                };
+                for nibble in 0..16 {
+                    let len = rest
+                        .iter()
+                        .take_while(|(key, _)| key[depth] == nibble)
+                        .count();
+                    let (children, remaining) = rest.split_at(len);
+                    if children.is_empty() {
+                        stream.append_empty_data();
+                    } else {
+                        append_child(&mut stream, &encode_node(children, depth + 1));
+                    }
+                    rest = remaining;
+                }
+                match terminal {
+                    Some((_, value)) => stream.append(&value.as_ref()),
+                    None => stream.append_empty_data(),
+                };
+            }
+        }
+    }
+    stream.out().to_vec()
+}
+
+/// Appends a reference to a child node: the node itself if its encoding is
+/// shorter than a hash, and its hash otherwise.
+fn append_child(stream: &mut RlpStream, encoded: &[u8]) {
+    if encoded.len() < 32 {
+        stream.append_raw(encoded, 1);
+    } else {
+        stream.append(&keccak(encoded));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use hex_literal::hex;
+
+    use super::*;
+
+    #[test]
+    fn it_computes_the_empty_root() {
+        assert_eq!(
+            ordered_trie_root(Vec::<Vec<u8>>::new()),
+            H256(hex!(
+                "56e81f171bcc55a6ff8345e692c0f86e5b48e01b996cadc001622fb5e363b421"
+            ))
+        );
+    }
+
+    #[test]
+    fn it_computes_trie_roots() {
+        let pairs = [
+            ("doe", "reindeer"),
+            ("dog", "puppy"),
+            ("dogglesworth", "cat"),
+        ];
+        assert_eq!(
+            trie_root(pairs),
+            H256(hex!(
+                "8aad789dff2f538bca5d8ea56e8abe10f4c7ba3a5dea95fea4cd6e7c3a1168d3"
+            ))
+        );
+    }
+
+    #[test]
+    fn it_computes_ordered_trie_roots() {
+        assert_eq!(
+            ordered_trie_root(["doe", "reindeer"]),
+            H256(hex!(
+                "e766d5d51b89dc39d981b41bda63248d7abce4f0225eefd023792a540bcffee3"
+            ))
+        );
+    }
+}
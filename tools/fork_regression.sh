@@ -0,0 +1,100 @@
+#!/bin/bash
+
+# Fetches the first block of every mainnet fork and generates its witness, to
+# report which forks the current code handles.
+#
+# Args:
+# 1 --> Rpc endpoint:port of a mainnet archive node (eg. http://35.246.1.96:8545)
+
+if [ -z "$1" ]; then
+    echo "Usage: $0 <MAINNET_ARCHIVE_NODE_ENDPOINT>" >&2
+    exit 2
+fi
+
+export RUST_BACKTRACE=1
+export RUST_MIN_STACK=8388608
+export RUST_LOG=mpt_trie=info,trace_decoder=info,plonky2=info,evm_arithmetization=trace,leader=info
+export RUSTFLAGS='-Ctarget-cpu=native'
+
+# Speciying smallest ranges, as we won't need them anyway.
+export ARITHMETIC_CIRCUIT_SIZE="16..17"
+export BYTE_PACKING_CIRCUIT_SIZE="9..10"
+export CPU_CIRCUIT_SIZE="12..13"
+export KECCAK_CIRCUIT_SIZE="14..15"
+export KECCAK_SPONGE_CIRCUIT_SIZE="9..10"
+export LOGIC_CIRCUIT_SIZE="12..13"
+export MEMORY_CIRCUIT_SIZE="17..18"
+
+OUTPUT_DIR="fork_regression"
+
+# <fork> <first block of the fork> <expected result>
+# `unknown` marks forks that have not been measured yet: their result is
+# reported but never counts as a regression. Replace it with the result of a
+# run against an archive node, and update it when support for a fork lands.
+# Only Cancun is known, as the fetcher refuses it.
+FORKS="
+frontier 1 unknown
+homestead 1150000 unknown
+dao 1920000 unknown
+tangerine_whistle 2463000 unknown
+spurious_dragon 2675000 unknown
+byzantium 4370000 unknown
+petersburg 7280000 unknown
+istanbul 9069000 unknown
+muir_glacier 9200000 unknown
+berlin 12244000 unknown
+london 12965000 unknown
+arrow_glacier 13773000 unknown
+gray_glacier 15050000 unknown
+paris 15537394 unknown
+shanghai 17034870 unknown
+cancun 19426587 unsupported
+"
+
+mkdir -p $OUTPUT_DIR
+cargo build --release --bin rpc || exit 1
+cargo build --release --features test_only --bin leader || exit 1
+
+regressions=0
+unmeasured=0
+while read -r fork block expected; do
+    if [ -z "$fork" ]; then
+        continue
+    fi
+
+    OUT_INPUT_PATH="${OUTPUT_DIR}/${fork}.json"
+    OUT_LOG_PATH="${OUTPUT_DIR}/${fork}.log"
+
+    if ! ./target/release/rpc fetch --rpc-url "$1" --chain mainnet --block $block --checkpoint $((block-1)) > $OUT_INPUT_PATH 2> $OUT_LOG_PATH; then
+        if grep -q "which the prover does not support" $OUT_LOG_PATH; then
+            result="unsupported"
+        else
+            result="fetch_failed"
+        fi
+    elif ! ./target/release/leader -n 1 --runtime in-memory stdio < $OUT_INPUT_PATH > /dev/null 2>> $OUT_LOG_PATH; then
+        result="witness_failed"
+    else
+        result="ok"
+    fi
+
+    if [ "$expected" = "unknown" ]; then
+        echo "${fork} (block ${block}): ${result}, not measured before. See ${OUT_LOG_PATH} for more details."
+        unmeasured=$((unmeasured+1))
+    elif [ "$result" = "$expected" ]; then
+        echo "${fork} (block ${block}): ${result}"
+        # Remove the input / log if the fork behaves as expected.
+        rm $OUT_INPUT_PATH $OUT_LOG_PATH
+    else
+        echo "${fork} (block ${block}): ${result}, expected ${expected}. See ${OUT_LOG_PATH} for more details."
+        regressions=$((regressions+1))
+    fi
+done <<< "$FORKS"
+
+if [ $unmeasured -ne 0 ]; then
+    echo "${unmeasured} fork(s) had no expected result; record the results above in the script."
+fi
+if [ $regressions -ne 0 ]; then
+    echo "${regressions} fork(s) did not behave as expected."
+    exit 1
+fi
+echo "All forks behaved as expected!"
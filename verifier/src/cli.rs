@@ -2,12 +2,30 @@ use std::path::PathBuf;
 
 use clap::{Parser, ValueHint};
 use common::prover_state::cli::CliProverStateConfig;
+use reqwest::Url;
 
 #[derive(Parser)]
 pub(crate) struct Cli {
     /// The file containing the proof to verify
-    #[arg(short, long, value_hint = ValueHint::FilePath)]
-    pub(crate) file_path: PathBuf,
+    #[arg(short, long, value_hint = ValueHint::FilePath, required_unless_present = "proof_dir")]
+    pub(crate) file_path: Option<PathBuf>,
+    /// Watch this directory and verify proofs as they appear in it, moving
+    /// the ones that fail to its `quarantine` subdirectory
+    #[arg(long, value_hint = ValueHint::DirPath, conflicts_with = "file_path")]
+    pub(crate) proof_dir: Option<PathBuf>,
+    /// How often, in seconds, the watched directory is scanned for new
+    /// proofs
+    #[arg(long, default_value_t = 10, requires = "proof_dir")]
+    pub(crate) poll_interval: u64,
+    /// POST the result of every verification in watch mode to this URL as
+    /// JSON
+    #[arg(long, value_hint = ValueHint::Url, requires = "proof_dir")]
+    pub(crate) webhook_url: Option<Url>,
+    /// Write the number of verified, failed and quarantined proofs in watch
+    /// mode to this file as JSON, replacing it after every verification. The
+    /// file is not taken for a proof if it is in the watched directory
+    #[arg(long, value_hint = ValueHint::FilePath, requires = "proof_dir")]
+    pub(crate) metrics_file: Option<PathBuf>,
     /// The prover configuration used to generate the preprocessed circuits
     /// and the verifier state.
     #[clap(flatten)]
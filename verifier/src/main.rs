@@ -1,33 +1,50 @@
-use std::fs::File;
+use std::{fs::File, path::Path, time::Duration};
 
 use anyhow::Result;
 use clap::Parser;
 use dotenvy::dotenv;
-use proof_gen::types::PlonkyProofIntern;
+use proof_gen::{types::PlonkyProofIntern, VerifierState};
 use serde_json::Deserializer;
 use tracing::info;
 
 mod cli;
 mod init;
+mod watch;
 
 fn main() -> Result<()> {
     dotenv().ok();
     init::tracing();
 
     let args = cli::Cli::parse();
-    let file = File::open(args.file_path)?;
-    let des = &mut Deserializer::from_reader(&file);
-    let input: PlonkyProofIntern = serde_path_to_error::deserialize(des)?;
 
     let verifer = args
         .prover_state_config
         .into_prover_state_manager()
         .verifier()?;
 
-    match verifer.verify(&input) {
+    if let Some(dir) = args.proof_dir {
+        return watch::watch(
+            &verifer,
+            &dir,
+            Duration::from_secs(args.poll_interval),
+            args.webhook_url.as_ref(),
+            args.metrics_file.as_deref(),
+        );
+    }
+
+    let file_path = args.file_path.expect("required unless watching");
+    match verify_file(&verifer, &file_path) {
         Ok(_) => info!("Proof verified successfully!"),
         Err(e) => info!("Proof verification failed with error: {:?}", e),
     };
 
     Ok(())
 }
+
+/// Reads a proof from a file and verifies it.
+pub(crate) fn verify_file(verifier: &VerifierState, path: &Path) -> Result<()> {
+    let file = File::open(path)?;
+    let des = &mut Deserializer::from_reader(&file);
+    let input: PlonkyProofIntern = serde_path_to_error::deserialize(des)?;
+    verifier.verify(&input)
+}
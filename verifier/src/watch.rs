@@ -0,0 +1,378 @@
+//! Watch mode: verifies proofs as they appear in a directory.
+use std::{
+    collections::{HashMap, HashSet},
+    ffi::OsString,
+    fs,
+    path::{Path, PathBuf},
+    thread,
+    time::Duration,
+};
+
+use anyhow::Result;
+use proof_gen::VerifierState;
+use reqwest::Url;
+use serde::Serialize;
+use tracing::{error, info, warn};
+
+use crate::verify_file;
+
+/// The subdirectory of the watched directory that failed proofs are moved to.
+const QUARANTINE_DIR: &str = "quarantine";
+
+/// The outcome of verifying a proof, as posted to the webhook.
+#[derive(Serialize, Debug)]
+struct VerificationReport<'a> {
+    file: &'a str,
+    verified: bool,
+    error: Option<String>,
+}
+
+/// Running totals of watch mode, written to the metrics file.
+#[derive(Serialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+struct WatchMetrics {
+    verified: u64,
+    failed: u64,
+    /// Failed proofs that were moved to the quarantine directory.
+    quarantined: u64,
+}
+
+/// Acts on the outcome of every verification.
+struct Watcher<'a> {
+    quarantine: PathBuf,
+    webhook: Option<(reqwest::blocking::Client, &'a Url)>,
+    metrics_file: Option<&'a Path>,
+    metrics: WatchMetrics,
+}
+
+impl Watcher<'_> {
+    /// Quarantines the proof at `path` if it failed to verify, reports the
+    /// outcome to the webhook and updates the metrics.
+    fn record(&mut self, path: &Path, result: Result<()>) {
+        let file = path.file_name().unwrap_or_default().to_string_lossy();
+        match &result {
+            Ok(()) => {
+                self.metrics.verified += 1;
+                info!("Verified {file} ({})", self.totals());
+            }
+            Err(e) => {
+                self.metrics.failed += 1;
+                error!("Failed to verify {file} ({}): {e:#}", self.totals());
+                match fs::rename(path, self.quarantine.join(&*file)) {
+                    Ok(()) => self.metrics.quarantined += 1,
+                    Err(e) => warn!("Failed to quarantine {file}: {e}"),
+                }
+            }
+        }
+
+        if let Some((client, url)) = &self.webhook {
+            let report = VerificationReport {
+                file: &file,
+                verified: result.is_ok(),
+                error: result.as_ref().err().map(|e| format!("{e:#}")),
+            };
+            let response = client
+                .post((*url).clone())
+                .json(&report)
+                .send()
+                .and_then(|response| response.error_for_status());
+            if let Err(e) = response {
+                warn!(
+                    "Failed to report {file} to the webhook: {}",
+                    e.without_url()
+                );
+            }
+        }
+
+        if let Some(metrics_file) = self.metrics_file {
+            if let Err(e) = self.write_metrics(metrics_file) {
+                warn!(
+                    "Failed to write metrics file {}: {e:#}",
+                    metrics_file.display()
+                );
+            }
+        }
+    }
+
+    fn totals(&self) -> String {
+        format!(
+            "{} verified, {} failed",
+            self.metrics.verified, self.metrics.failed
+        )
+    }
+
+    /// Atomically replaces the metrics file with the current totals.
+    fn write_metrics(&self, path: &Path) -> Result<()> {
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, serde_json::to_vec(&self.metrics)?)?;
+        fs::rename(tmp, path)?;
+        Ok(())
+    }
+}
+
+/// Verifies every proof in `dir`, then keeps scanning it every
+/// `poll_interval` for new ones.
+///
+/// Proofs that fail to verify are moved to the `quarantine` subdirectory. A
+/// file is only picked up once its size is the same in two consecutive scans,
+/// so that proofs still being written are not reported as failures. A scan
+/// that fails to read the directory or one of its entries is retried at the
+/// next poll.
+pub(crate) fn watch(
+    verifier: &VerifierState,
+    dir: &Path,
+    poll_interval: Duration,
+    webhook_url: Option<&Url>,
+    metrics_file: Option<&Path>,
+) -> Result<()> {
+    let quarantine = dir.join(QUARANTINE_DIR);
+    fs::create_dir_all(&quarantine)?;
+    let mut watcher = Watcher {
+        quarantine,
+        webhook: webhook_url.map(|url| (reqwest::blocking::Client::new(), url)),
+        metrics_file,
+        metrics: WatchMetrics::default(),
+    };
+    let ignored = own_files(dir, metrics_file)?;
+    info!("Watching {} for proofs", dir.display());
+
+    let mut done = HashSet::new();
+    let mut sizes = HashMap::new();
+    loop {
+        for path in settled_proofs(dir, &done, &ignored, &mut sizes) {
+            watcher.record(&path, verify_file(verifier, &path));
+            done.insert(path);
+        }
+        thread::sleep(poll_interval);
+    }
+}
+
+/// Returns the names of the files in `dir` that watch mode writes itself:
+/// the metrics file and its temporary file, if they are in `dir`.
+fn own_files(dir: &Path, metrics_file: Option<&Path>) -> Result<HashSet<OsString>> {
+    let mut names = HashSet::new();
+    let Some(metrics_file) = metrics_file else {
+        return Ok(names);
+    };
+    let parent = match metrics_file.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    if fs::canonicalize(parent).ok() == Some(fs::canonicalize(dir)?) {
+        for path in [
+            metrics_file.to_path_buf(),
+            metrics_file.with_extension("tmp"),
+        ] {
+            names.extend(path.file_name().map(ToOwned::to_owned));
+        }
+    }
+    Ok(names)
+}
+
+/// Returns the proofs in `dir` that have not been verified yet and whose size
+/// has not changed since the previous scan.
+///
+/// Files named in `ignored` are skipped. Entries that cannot be read are
+/// skipped with a warning, and so is the whole scan if `dir` cannot be read.
+fn settled_proofs(
+    dir: &Path,
+    done: &HashSet<PathBuf>,
+    ignored: &HashSet<OsString>,
+    sizes: &mut HashMap<PathBuf, u64>,
+) -> Vec<PathBuf> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) => {
+            warn!(
+                "Failed to scan {}, retrying at the next poll: {e}",
+                dir.display()
+            );
+            return Vec::new();
+        }
+    };
+    let mut settled = Vec::new();
+    for entry in entries {
+        let (path, metadata) = match entry.and_then(|entry| Ok((entry.path(), entry.metadata()?))) {
+            Ok(entry) => entry,
+            Err(e) => {
+                warn!(
+                    "Failed to read an entry of {}, retrying at the next poll: {e}",
+                    dir.display()
+                );
+                continue;
+            }
+        };
+        if !metadata.is_file()
+            || path.extension().is_none_or(|extension| extension != "json")
+            || path.file_name().is_some_and(|name| ignored.contains(name))
+            || done.contains(&path)
+        {
+            continue;
+        }
+        if sizes.insert(path.clone(), metadata.len()) == Some(metadata.len()) {
+            sizes.remove(&path);
+            settled.push(path);
+        }
+    }
+    settled.sort();
+    settled
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        io::{BufRead, BufReader, Read, Write},
+        net::TcpListener,
+        sync::mpsc,
+    };
+
+    use anyhow::anyhow;
+    use serde_json::{json, Value};
+
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("verifier-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join(QUARANTINE_DIR)).unwrap();
+        dir
+    }
+
+    /// Accepts a single HTTP request, answers it with `204 No Content` and
+    /// sends its body to the returned channel.
+    fn webhook() -> (Url, mpsc::Receiver<Value>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap())
+            .parse()
+            .unwrap();
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut stream = BufReader::new(stream);
+            let mut content_length = 0;
+            loop {
+                let mut line = String::new();
+                stream.read_line(&mut line).unwrap();
+                let line = line.trim_end();
+                if line.is_empty() {
+                    break;
+                }
+                if let Some((name, value)) = line.split_once(':') {
+                    if name.eq_ignore_ascii_case("content-length") {
+                        content_length = value.trim().parse().unwrap();
+                    }
+                }
+            }
+            let mut body = vec![0; content_length];
+            stream.read_exact(&mut body).unwrap();
+            stream
+                .get_mut()
+                .write_all(b"HTTP/1.1 204 No Content\r\nconnection: close\r\n\r\n")
+                .unwrap();
+            sender.send(serde_json::from_slice(&body).unwrap()).unwrap();
+        });
+        (url, receiver)
+    }
+
+    #[test]
+    fn it_quarantines_failed_proofs_and_reports_them() {
+        let dir = temp_dir("quarantine");
+        let (url, reports) = webhook();
+        let metrics_file = dir.join("metrics.json");
+        let mut watcher = Watcher {
+            quarantine: dir.join(QUARANTINE_DIR),
+            webhook: Some((reqwest::blocking::Client::new(), &url)),
+            metrics_file: Some(&metrics_file),
+            metrics: WatchMetrics::default(),
+        };
+        let proof = dir.join("proof_1.json");
+        fs::write(&proof, "{}").unwrap();
+
+        watcher.record(&proof, Err(anyhow!("bad proof")));
+
+        assert!(!proof.exists());
+        assert!(dir.join(QUARANTINE_DIR).join("proof_1.json").exists());
+        assert_eq!(
+            reports.recv().unwrap(),
+            json!({"file": "proof_1.json", "verified": false, "error": "bad proof"})
+        );
+        let expected = WatchMetrics {
+            verified: 0,
+            failed: 1,
+            quarantined: 1,
+        };
+        assert_eq!(watcher.metrics, expected);
+        let written: Value = serde_json::from_slice(&fs::read(&metrics_file).unwrap()).unwrap();
+        assert_eq!(
+            written,
+            json!({"verified": 0, "failed": 1, "quarantined": 1})
+        );
+    }
+
+    #[test]
+    fn it_leaves_verified_proofs_in_place() {
+        let dir = temp_dir("verified");
+        let (url, reports) = webhook();
+        let mut watcher = Watcher {
+            quarantine: dir.join(QUARANTINE_DIR),
+            webhook: Some((reqwest::blocking::Client::new(), &url)),
+            metrics_file: None,
+            metrics: WatchMetrics::default(),
+        };
+        let proof = dir.join("proof_2.json");
+        fs::write(&proof, "{}").unwrap();
+
+        watcher.record(&proof, Ok(()));
+
+        assert!(proof.exists());
+        assert_eq!(
+            reports.recv().unwrap(),
+            json!({"file": "proof_2.json", "verified": true, "error": null})
+        );
+        assert_eq!(watcher.metrics.verified, 1);
+        assert_eq!(watcher.metrics.quarantined, 0);
+    }
+
+    #[test]
+    fn it_counts_failures_that_cannot_be_quarantined() {
+        let dir = temp_dir("missing");
+        let mut watcher = Watcher {
+            quarantine: dir.join(QUARANTINE_DIR),
+            webhook: None,
+            metrics_file: None,
+            metrics: WatchMetrics::default(),
+        };
+
+        watcher.record(&dir.join("gone.json"), Err(anyhow!("unreadable")));
+
+        assert_eq!(watcher.metrics.failed, 1);
+        assert_eq!(watcher.metrics.quarantined, 0);
+    }
+
+    #[test]
+    fn it_only_picks_up_settled_proofs() {
+        let dir = temp_dir("settled");
+        let metrics_file = dir.join("metrics.json");
+        let ignored = own_files(&dir, Some(&metrics_file)).unwrap();
+        fs::write(&metrics_file, "{}").unwrap();
+        fs::write(metrics_file.with_extension("tmp"), "{}").unwrap();
+        let proof = dir.join("proof_3.json");
+        fs::write(&proof, "{}").unwrap();
+
+        let mut sizes = HashMap::new();
+        let mut scan = || settled_proofs(&dir, &HashSet::new(), &ignored, &mut sizes);
+        assert_eq!(scan(), Vec::<PathBuf>::new());
+        assert_eq!(scan(), [proof]);
+
+        // A directory that cannot be read is retried rather than fatal.
+        let missing = dir.join("missing");
+        assert!(settled_proofs(&missing, &HashSet::new(), &ignored, &mut sizes).is_empty());
+    }
+
+    #[test]
+    fn it_ignores_metrics_files_outside_of_the_watched_directory() {
+        let dir = temp_dir("elsewhere");
+        let elsewhere = dir.join(QUARANTINE_DIR).join("metrics.json");
+        assert!(own_files(&dir, Some(&elsewhere)).unwrap().is_empty());
+        assert!(own_files(&dir, None).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,78 @@
+//! Fetches the prover inputs of a range of blocks and uploads them to an S3
+//! bucket as `<PREFIX>block-<NUMBER>.json`, for workers that prove from
+//! object storage.
+//!
+//! AWS credentials and the region are taken from the environment, as by the
+//! AWS CLI.
+//!
+//! ```bash
+//! cargo run --release --features s3-example --example fetch_to_s3 -- \
+//!     --rpc-url <RPC_URL> --bucket <BUCKET> --start-block 100 --end-block 110
+//! ```
+use anyhow::Result;
+use aws_sdk_s3::primitives::ByteStream;
+use clap::Parser;
+use rpc::{client::RpcArgs, fetch_prover_input, FetchProverInputRequest};
+use tokio_util::sync::CancellationToken;
+use tracing::info;
+use zero_bin::PipelineConfig;
+
+#[derive(Parser)]
+struct Cli {
+    #[command(flatten)]
+    rpc: RpcArgs,
+    /// The first block to fetch
+    #[arg(long)]
+    start_block: u64,
+    /// The last block to fetch
+    #[arg(long)]
+    end_block: u64,
+    #[arg(long)]
+    bucket: String,
+    /// Prepended to the key of every object
+    #[arg(long, default_value = "")]
+    prefix: String,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    tracing_subscriber::fmt::init();
+    let cli = Cli::parse();
+
+    // Fetching needs no paladin runtime, so this uses the rpc crate directly
+    // rather than a `Pipeline`.
+    let profile = cli.rpc.profile()?;
+    let client = cli.rpc.client(&profile)?;
+    let config = PipelineConfig {
+        chain_spec: profile.chain_spec.unwrap_or_default(),
+        ..Default::default()
+    };
+    let s3 = aws_sdk_s3::Client::new(
+        &aws_config::load_defaults(aws_config::BehaviorVersion::latest()).await,
+    );
+
+    let cancel = CancellationToken::new();
+    for block_number in cli.start_block..=cli.end_block {
+        let prover_input = fetch_prover_input(FetchProverInputRequest {
+            client: &client,
+            block_id: block_number.into(),
+            checkpoint_block_id: config.checkpoint_block_id,
+            chain_spec: &config.chain_spec,
+            header_defaults: &config.header_defaults,
+            prev_hash_concurrency: cli.rpc.prev_hash_concurrency,
+            cancel: &cancel,
+        })
+        .await?;
+
+        let key = format!("{}block-{block_number}.json", cli.prefix);
+        s3.put_object()
+            .bucket(&cli.bucket)
+            .key(&key)
+            .content_type("application/json")
+            .body(ByteStream::from(serde_json::to_vec(&prover_input)?))
+            .send()
+            .await?;
+        info!("Uploaded block {block_number} to s3://{}/{key}", cli.bucket);
+    }
+    Ok(())
+}
@@ -0,0 +1,66 @@
+//! Proves every new block of the chain, each on top of the proof of its
+//! parent, and writes the proofs to `<OUTPUT_DIR>/proof-<NUMBER>.json`.
+//!
+//! ```bash
+//! cargo run --release --example follow_and_prove -- \
+//!     --rpc-url <RPC_URL> --runtime in-memory --output-dir ./proofs
+//! ```
+use std::{fs, path::PathBuf, time::Duration};
+
+use anyhow::Result;
+use clap::{Parser, ValueHint};
+use tracing::info;
+use zero_bin::Pipeline;
+
+mod support;
+
+#[derive(Parser)]
+struct Cli {
+    #[command(flatten)]
+    setup: support::SetupArgs,
+    /// The first block to prove; defaults to the latest confirmed block
+    #[arg(long)]
+    start_block: Option<u64>,
+    /// How many blocks a block must be behind the head before it is proved
+    #[arg(long, default_value_t = 2)]
+    confirmations: u64,
+    /// How often, in seconds, the head is polled
+    #[arg(long, default_value_t = 4)]
+    poll_interval: u64,
+    #[arg(long, value_hint = ValueHint::DirPath)]
+    output_dir: PathBuf,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    tracing_subscriber::fmt::init();
+    let cli = Cli::parse();
+    fs::create_dir_all(&cli.output_dir)?;
+
+    let (client, config) = cli.setup.client()?;
+    // The client is cheap to clone; this one polls the head.
+    let head_client = client.clone();
+    let pipeline = Pipeline::new(cli.setup.runtime().await?, client, config);
+
+    let mut next = match cli.start_block {
+        Some(start_block) => start_block,
+        None => rpc::fetch_head(&head_client)
+            .await?
+            .saturating_sub(cli.confirmations),
+    };
+    // The first block is proved without the proof of its parent, so its
+    // proof does not extend any earlier chain of proofs.
+    let mut previous = None;
+    loop {
+        let head = rpc::fetch_head(&head_client).await?;
+        while next + cli.confirmations <= head {
+            let proof = pipeline.prove_block(next.into(), previous).await?;
+            let path = cli.output_dir.join(format!("proof-{next}.json"));
+            fs::write(&path, serde_json::to_vec(&proof.intern)?)?;
+            info!("Wrote the proof of block {next} to {}", path.display());
+            previous = Some(proof.intern);
+            next += 1;
+        }
+        tokio::time::sleep(Duration::from_secs(cli.poll_interval)).await;
+    }
+}
@@ -0,0 +1,51 @@
+//! Fetches and proves a single block, and writes its proof to stdout.
+//!
+//! ```bash
+//! cargo run --release --example prove_one_block -- \
+//!     --rpc-url <RPC_URL> --runtime in-memory --block <BLOCK>
+//! ```
+use std::{io::Write, path::PathBuf};
+
+use anyhow::Result;
+use clap::{Parser, ValueHint};
+use proof_gen::types::PlonkyProofIntern;
+use tracing_subscriber::EnvFilter;
+use zero_bin::{BlockId, Pipeline};
+
+mod support;
+
+#[derive(Parser)]
+struct Cli {
+    #[command(flatten)]
+    setup: support::SetupArgs,
+    /// The block to prove: a number, a hash, or a tag
+    #[arg(short, long)]
+    block: BlockId,
+    /// The proof of the block's parent, as written by this example or the
+    /// leader
+    #[arg(short = 'f', long, value_hint = ValueHint::FilePath)]
+    previous_proof: Option<PathBuf>,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    // The proof goes to stdout, so log to stderr.
+    tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::from_default_env())
+        .with_writer(std::io::stderr)
+        .init();
+    let cli = Cli::parse();
+
+    let previous: Option<PlonkyProofIntern> = cli
+        .previous_proof
+        .map(|path| anyhow::Ok(serde_json::from_slice(&std::fs::read(path)?)?))
+        .transpose()?;
+    let (client, config) = cli.setup.client()?;
+    let pipeline = Pipeline::new(cli.setup.runtime().await?, client, config);
+
+    let proof = pipeline.prove_block(cli.block, previous).await;
+    pipeline.close().await?;
+
+    std::io::stdout().write_all(&serde_json::to_vec(&proof?.intern)?)?;
+    Ok(())
+}
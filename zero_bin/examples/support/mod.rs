@@ -0,0 +1,45 @@
+//! Setup shared by the examples: the node and paladin runtime options, taken
+//! the same way as the leader takes them.
+use anyhow::Result;
+use clap::Args;
+use common::prover_state::{cli::CliProverStateConfig, TableLoadStrategy};
+use paladin::runtime::Runtime;
+use rpc::client::RpcArgs;
+use zero_bin::{PipelineConfig, RpcClient};
+
+#[derive(Args)]
+pub struct SetupArgs {
+    #[command(flatten)]
+    pub rpc: RpcArgs,
+    #[command(flatten)]
+    pub paladin: paladin::config::Config,
+    #[command(flatten)]
+    pub prover_state_config: CliProverStateConfig,
+}
+
+impl SetupArgs {
+    /// Builds the RPC client, and a pipeline configuration using the chain
+    /// spec of its profile, if any.
+    pub fn client(&self) -> Result<(RpcClient, PipelineConfig)> {
+        let profile = self.rpc.profile()?;
+        let config = PipelineConfig {
+            chain_spec: profile.chain_spec.clone().unwrap_or_default(),
+            prev_hash_concurrency: self.rpc.prev_hash_concurrency,
+            ..Default::default()
+        };
+        Ok((self.rpc.client(&profile)?, config))
+    }
+
+    /// Starts the paladin runtime, initializing the prover state first when
+    /// running in memory.
+    pub async fn runtime(self) -> Result<Runtime> {
+        if let paladin::config::Runtime::InMemory = self.paladin.runtime {
+            self.prover_state_config
+                .into_prover_state_manager()
+                .with_load_strategy(TableLoadStrategy::Monolithic)
+                .initialize()?;
+        }
+        let runtime = Runtime::from_config(&self.paladin, ops::register()).await?;
+        Ok(runtime)
+    }
+}
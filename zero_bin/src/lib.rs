@@ -0,0 +1,198 @@
+//! The fetch, decode and prove pipeline as a library, for embedding zero-bin
+//! in other software instead of orchestrating the `rpc` and `leader`
+//! binaries.
+//!
+//! ```ignore
+//! let pipeline = Pipeline::new(runtime, client, PipelineConfig::default());
+//! let range = pipeline.prove_range(100..=110, None).await?;
+//! pipeline.close().await?;
+//! ```
+use std::{num::NonZeroUsize, ops::RangeInclusive};
+
+use anyhow::Result;
+use ethereum_types::H256;
+use paladin::runtime::Runtime;
+use proof_gen::{proof_types::GeneratedBlockProof, types::PlonkyProofIntern};
+use prover::ProverInput;
+pub use rpc::{
+    block_id::BlockId, chain_spec::ChainSpec, client::RpcClient, header_defaults::HeaderDefaults,
+};
+use rpc::{
+    fetch_commitment_of_blocks, fetch_prover_input, fetch_range_commitment, FetchProverInputRequest,
+};
+use tokio_util::sync::CancellationToken;
+use tracing::info;
+
+/// How blocks are fetched from the node.
+#[derive(Debug, Clone)]
+pub struct PipelineConfig {
+    /// The block whose state root is used as the checkpoint.
+    pub checkpoint_block_id: BlockId,
+    pub chain_spec: ChainSpec,
+    pub header_defaults: HeaderDefaults,
+    /// The maximum number of block headers fetched concurrently when
+    /// collecting the hashes of previous blocks.
+    pub prev_hash_concurrency: NonZeroUsize,
+}
+
+impl Default for PipelineConfig {
+    fn default() -> Self {
+        Self {
+            checkpoint_block_id: BlockId::Number(0),
+            chain_spec: ChainSpec::default(),
+            header_defaults: HeaderDefaults::default(),
+            prev_hash_concurrency: NonZeroUsize::new(16).unwrap(),
+        }
+    }
+}
+
+/// The proofs of a range of blocks.
+#[derive(Debug)]
+pub struct RangeProof {
+    /// The block proofs, in block order.
+    pub proofs: Vec<GeneratedBlockProof>,
+    /// The [hash-chain commitment](rpc::commitment) over the hashes and state
+    /// roots of the proven blocks, which consumers can recompute from the
+    /// headers with `rpc commit-range`.
+    pub commitment: H256,
+}
+
+/// Callbacks invoked as blocks move through a [`Pipeline`].
+///
+/// All methods default to doing nothing.
+pub trait PipelineHooks: Send + Sync {
+    /// Called once the prover input of a block has been fetched.
+    fn on_fetched(&self, _input: &ProverInput) {}
+    /// Called once a block has been proved.
+    fn on_proved(&self, _proof: &GeneratedBlockProof) {}
+    /// Called when fetching or proving a block fails.
+    fn on_failed(&self, _block_id: BlockId, _error: &anyhow::Error) {}
+}
+
+impl PipelineHooks for () {}
+
+/// Fetches blocks from a node and proves them on a paladin runtime.
+pub struct Pipeline {
+    runtime: Runtime,
+    client: RpcClient,
+    config: PipelineConfig,
+    hooks: Box<dyn PipelineHooks>,
+    cancel: CancellationToken,
+}
+
+impl Pipeline {
+    pub fn new(runtime: Runtime, client: RpcClient, config: PipelineConfig) -> Self {
+        Self {
+            runtime,
+            client,
+            config,
+            hooks: Box::new(()),
+            cancel: CancellationToken::new(),
+        }
+    }
+
+    /// Returns a token that aborts all fetching and proving in progress, and
+    /// any started afterwards, when cancelled.
+    pub fn cancellation_token(&self) -> CancellationToken {
+        self.cancel.clone()
+    }
+
+    /// Replaces the pipeline's hooks.
+    pub fn with_hooks<H: PipelineHooks + 'static>(mut self, hooks: H) -> Self {
+        self.hooks = Box::new(hooks);
+        self
+    }
+
+    /// Fetches the prover input of a block.
+    pub async fn fetch_block(&self, block_id: BlockId) -> Result<ProverInput> {
+        let input = fetch_prover_input(FetchProverInputRequest {
+            client: &self.client,
+            block_id,
+            checkpoint_block_id: self.config.checkpoint_block_id,
+            chain_spec: &self.config.chain_spec,
+            header_defaults: &self.config.header_defaults,
+            prev_hash_concurrency: self.config.prev_hash_concurrency,
+            cancel: &self.cancel,
+        })
+        .await;
+
+        match &input {
+            Ok(input) => self.hooks.on_fetched(input),
+            Err(e) => self.hooks.on_failed(block_id, e),
+        }
+        input
+    }
+
+    /// Fetches and proves a block, on top of the proof of its parent if
+    /// given.
+    pub async fn prove_block(
+        &self,
+        block_id: BlockId,
+        previous: Option<PlonkyProofIntern>,
+    ) -> Result<GeneratedBlockProof> {
+        let input = self.fetch_block(block_id).await?;
+        self.prove_input(block_id, input, previous).await
+    }
+
+    async fn prove_input(
+        &self,
+        block_id: BlockId,
+        input: ProverInput,
+        previous: Option<PlonkyProofIntern>,
+    ) -> Result<GeneratedBlockProof> {
+        let proof = input.prove(&self.runtime, previous, &self.cancel).await;
+
+        match &proof {
+            Ok(proof) => self.hooks.on_proved(proof),
+            Err(e) => self.hooks.on_failed(block_id, e),
+        }
+        proof
+    }
+
+    /// Proves a range of blocks in order, each on top of the proof of the one
+    /// before it. `previous` is the proof of the block preceding the range,
+    /// if any.
+    ///
+    /// Stops at the first block that fails, and fails if the chain
+    /// reorganized while proving, so that the commitment is over the proven
+    /// blocks.
+    pub async fn prove_range(
+        &self,
+        blocks: RangeInclusive<u64>,
+        mut previous: Option<PlonkyProofIntern>,
+    ) -> Result<RangeProof> {
+        info!("Proving blocks {}..={}", blocks.start(), blocks.end());
+
+        let mut proofs = Vec::new();
+        let mut hashes = Vec::new();
+        for block_number in blocks.clone() {
+            let input = self.fetch_block(block_number.into()).await?;
+            hashes.push(input.other_data.b_data.b_hashes.cur_hash);
+            let proof = self
+                .prove_input(block_number.into(), input, previous)
+                .await?;
+            previous = Some(proof.intern.clone());
+            proofs.push(proof);
+        }
+
+        let commitment = fetch_commitment_of_blocks(
+            &self.client,
+            *blocks.start(),
+            &hashes,
+            self.config.prev_hash_concurrency,
+        )
+        .await?;
+        Ok(RangeProof { proofs, commitment })
+    }
+
+    /// Computes the hash-chain commitment over a range of blocks from their
+    /// headers.
+    pub async fn range_commitment(&self, blocks: RangeInclusive<u64>) -> Result<H256> {
+        fetch_range_commitment(&self.client, blocks, self.config.prev_hash_concurrency).await
+    }
+
+    /// Shuts down the pipeline's runtime.
+    pub async fn close(self) -> Result<()> {
+        self.runtime.close().await
+    }
+}